@@ -0,0 +1,70 @@
+// Vertex shader
+
+
+struct Locals {
+    screen_size: vec2<f32>,
+    _padding: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> locals: Locals;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    // sRGB gamma 0-1
+    @location(1) color: vec4<f32>,
+};
+
+fn unpack_color(color: u32) -> vec4<f32> {
+    return vec4<f32>(
+        f32(color & 255u),
+        f32((color >> 8u) & 255u),
+        f32((color >> 16u) & 255u),
+        f32((color >> 24u) & 255u),
+    ) / 255.0;
+}
+
+@vertex
+fn vs_main(
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) color: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(
+        2.0 * position.x / locals.screen_size.x - 1.0,
+        1.0 - 2.0 * position.y / locals.screen_size.y,
+        0.0,
+        1.0,
+    );
+    out.tex_coords = tex_coords;
+    out.color = unpack_color(color);
+    return out;
+}
+
+// Fragment shader
+
+@group(1) @binding(0)
+var texture: texture_2d<f32>;
+
+@group(1) @binding(1)
+var texture_sampler: sampler;
+
+fn linear_from_gamma_rgb(srgb: vec3<f32>) -> vec3<f32> {
+    let cutoff = srgb < vec3<f32>(0.04045);
+    let lower = srgb / vec3<f32>(12.92);
+    let higher = pow((srgb + vec3<f32>(0.055)) / vec3<f32>(1.055), vec3<f32>(2.4));
+    return select(higher, lower, cutoff);
+}
+
+@fragment
+fn fs_main_linear_framebuffer(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = in.color * textureSample(texture, texture_sampler, in.tex_coords);
+    return vec4<f32>(linear_from_gamma_rgb(color.rgb), color.a);
+}
+
+@fragment
+fn fs_main_gamma_framebuffer(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color * textureSample(texture, texture_sampler, in.tex_coords);
+}
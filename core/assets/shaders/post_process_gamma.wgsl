@@ -0,0 +1,15 @@
+// Fragment shader
+// params.x: gamma, params.y: exposure
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(source_texture, source_sampler, in.tex_coords);
+
+    let exposed = vec3<f32>(1.0) - exp(-color.rgb * params.y);
+
+    // sRGB targets already encode with a ~2.2 gamma, only the difference with `gamma` is applied
+    let target_gamma = select(1.0, 2.2, output_srgb > 0.5);
+    let corrected = pow(exposed, vec3<f32>(target_gamma / params.x));
+
+    return vec4<f32>(corrected, color.a);
+}
@@ -57,7 +57,7 @@ fn vs_main(
 @group(1) @binding(0)
 var texture: texture_2d<f32>;
 
-@group(1) @binding(1)
+@group(2) @binding(0)
 var texture_sampler: sampler;
 
 @fragment
@@ -65,3 +65,13 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     return textureSample(texture, texture_sampler, in.tex_coords) * in.color;
     //return in.color;
 }
+
+@group(3) @binding(0)
+var mask_texture: texture_2d<f32>;
+
+@fragment
+fn fs_main_masked(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(texture, texture_sampler, in.tex_coords) * in.color;
+    let mask = textureSample(mask_texture, texture_sampler, in.tex_coords).r;
+    return vec4<f32>(color.rgb, color.a * mask);
+}
@@ -0,0 +1,74 @@
+// Vertex shader
+
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+};
+
+// Shape is the quad size, the corner radius and the edge softness in pixels
+struct SdfInstanceInput {
+    @location(2) model_matrix_0: vec4<f32>,
+    @location(3) model_matrix_1: vec4<f32>,
+    @location(4) model_matrix_2: vec4<f32>,
+    @location(5) model_matrix_3: vec4<f32>,
+
+    @location(6) color: vec4<f32>,
+    @location(7) shape: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    // Position from the quad center in world units
+    @location(1) local_position: vec2<f32>,
+    @location(2) shape: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: SdfInstanceInput
+) -> VertexOutput {
+
+    let model_matrix = mat4x4<f32> (
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3
+    );
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * model_matrix * vec4<f32>(model.position, 0.0, 1.0);
+    out.color = instance.color;
+    out.local_position = (model.position - vec2<f32>(0.5)) * instance.shape.xy;
+    out.shape = instance.shape;
+    return out;
+}
+
+// Fragment shader
+
+fn rounded_rect_distance(position: vec2<f32>, half_size: vec2<f32>, radius: f32) -> f32 {
+    let q = abs(position) - half_size + vec2<f32>(radius);
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let half_size = in.shape.xy * 0.5;
+    let radius = clamp(in.shape.z, 0.0, min(half_size.x, half_size.y));
+    let distance = rounded_rect_distance(in.local_position, half_size, radius);
+
+    // fwidth is the distance change over one pixel, the edge spans `softness` pixels at any zoom
+    let edge_width = max(in.shape.w * fwidth(distance), 1e-5);
+    let coverage = clamp(0.5 - distance / edge_width, 0.0, 1.0);
+
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
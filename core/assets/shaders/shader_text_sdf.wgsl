@@ -0,0 +1,76 @@
+// Vertex shader
+
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+};
+
+// The glyph atlas alpha is the distance to the glyph edge, 0.5 on the edge
+struct SdfTextInstanceInput {
+    @location(2) model_matrix_0: vec4<f32>,
+    @location(3) model_matrix_1: vec4<f32>,
+    @location(4) model_matrix_2: vec4<f32>,
+    @location(5) model_matrix_3: vec4<f32>,
+
+    @location(6) color: vec4<f32>,
+
+    @location(7) tex_coords_size: vec2<f32>,
+    @location(8) tex_coords_offset: vec2<f32>,
+    @location(9) edge_softness: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) edge_softness: f32,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: SdfTextInstanceInput
+) -> VertexOutput {
+
+    let model_matrix = mat4x4<f32> (
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3
+    );
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * model_matrix * vec4<f32>(model.position, 0.0, 1.0);
+
+    out.color = instance.color;
+    out.tex_coords = model.tex_coords * instance.tex_coords_size + instance.tex_coords_offset;
+    out.edge_softness = instance.edge_softness;
+    return out;
+}
+
+// Fragment shader
+
+@group(1) @binding(0)
+var texture: texture_2d<f32>;
+
+@group(2) @binding(0)
+var texture_sampler: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let distance = textureSample(texture, texture_sampler, in.tex_coords).a;
+
+    // fwidth is the distance change over one pixel, the edge spans `edge_softness` pixels at any scale
+    let edge_width = max(0.5 * in.edge_softness * fwidth(distance), 1e-5);
+    let coverage = smoothstep(0.5 - edge_width, 0.5 + edge_width, distance);
+
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
@@ -0,0 +1,17 @@
+// Fills the render pass viewport with a color, the letterbox bars around it keep their clear color
+
+
+@group(0) @binding(0)
+var<uniform> clear_color: vec4<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+
+    return vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 1.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return clear_color;
+}
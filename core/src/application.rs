@@ -1,16 +1,187 @@
-use std::sync::{Arc, Mutex};
+use std::{sync::{Arc, Mutex}, time::{Duration, Instant}};
 
-use winit::{event::WindowEvent, event_loop::{ActiveEventLoop, EventLoop}, window::{Window, WindowAttributes}};
+use winit::{dpi::LogicalSize, event::{StartCause, WindowEvent}, event_loop::{ActiveEventLoop, ControlFlow, EventLoop}, keyboard::KeyCode, window::{Fullscreen, Window, WindowAttributes}};
 
-use crate::{application::event::{ApplicationEvent, ApplicationSignal}, assets::{texture::Texture2D, AssetsManager, AssetsManagerRef}, graphics::GraphicsContext, Timer};
+use crate::{application::{command::{ApplicationCommand, CommandBuffer}, event::{ApplicationEvent, ApplicationSignal, FrameTime, TimedEvent}, input::TouchMouseEmulation, layer::{EventLayer, LayerStack}, replay::{InputPlayer, InputRecorderRef}}, assets::{texture::Texture2D, AssetsManager, AssetsManagerRef}, graphics::{GraphicsContext, GraphicsContextSettings}, engine_time, Timer};
 
+pub mod command;
 pub mod event;
+pub mod input;
+pub mod layer;
+pub mod replay;
+pub mod state;
 
 pub trait ApplicationHandler {
     fn init(context: &GraphicsContext, assets_manager: AssetsManagerRef) -> Self;
-    fn update(&mut self, dt: f32) -> ApplicationSignal;
+    fn update(&mut self, dt: f32, commands: &mut CommandBuffer) -> ApplicationSignal;
+
+    // Called instead of `update`, override it to get the time since the start without drift
+    fn update_timed(&mut self, time: FrameTime, commands: &mut CommandBuffer) -> ApplicationSignal {
+        self.update(time.dt(), commands)
+    }
     fn draw(&mut self, context: &GraphicsContext) -> Result<(), wgpu::SurfaceError>;
-    fn handle_event(&mut self, event: ApplicationEvent, dt: f32) -> ApplicationSignal;
+
+    // Called `ApplicationSettings::fixed_update_rate` times per second with a constant `dt`,
+    // before `update` in the frames where steps are due
+    fn fixed_update(&mut self, _dt: f32, _commands: &mut CommandBuffer) -> ApplicationSignal {
+        ApplicationSignal::Continue
+    }
+
+    // The interpolation is the fraction (0..1) of a fixed step elapsed since the last `fixed_update`,
+    // used to blend the previous and current fixed state. Always 1 without a fixed update rate
+    fn draw_interpolated(&mut self, context: &GraphicsContext, _interpolation: f32) -> Result<(), wgpu::SurfaceError> {
+        self.draw(context)
+    }
+    fn handle_event(&mut self, event: ApplicationEvent, dt: f32, commands: &mut CommandBuffer) -> ApplicationSignal;
+
+    // Receives the main window events first, override it instead of `handle_event` to know when they happened
+    fn handle_timed_event(&mut self, event: TimedEvent, dt: f32, commands: &mut CommandBuffer) -> ApplicationSignal {
+        self.handle_event(event.event, dt, commands)
+    }
+
+    // Secondary windows added with `Application::with_window` are identified by the order they were added in,
+    // their contexts share the main context device so assets can be drawn in any window
+    fn draw_window(&mut self, _window: usize, _context: &GraphicsContext) -> Result<(), wgpu::SurfaceError> {
+        Ok(())
+    }
+
+    fn handle_window_event(&mut self, _window: usize, _event: ApplicationEvent, _dt: f32, _commands: &mut CommandBuffer) -> ApplicationSignal {
+        ApplicationSignal::Continue
+    }
+
+    // Sees the main window events before they are translated, including the ones `ApplicationEvent`
+    // does not model such as touch. Returning true consumes the event: it is not passed to egui, the
+    // handler and the layers, but resizes, scale factor and focus changes are still applied to the
+    // surface and the frame throttling. A consumed close request keeps the window open. Redraw
+    // requests are not passed
+    fn handle_raw_event(&mut self, _event: &WindowEvent) -> bool {
+        false
+    }
+
+    // Called after the file textures are reloaded by `ApplicationSettings::reload_key` or
+    // `CommandBuffer::reload`, rebuilds the state read from disk such as levels
+    fn on_reload(&mut self, _context: &GraphicsContext) {}
+
+    #[cfg(feature = "egui")]
+    fn run_ui(&mut self, _ctx: &egui::Context) {}
+}
+
+
+#[derive(Debug, Clone)]
+pub struct ApplicationSettings {
+    pub title: String,
+    // Logical size, the surface is created with the physical size of the window
+    pub width: u32,
+    pub height: u32,
+    pub resizable: bool,
+    // Logical (width, height) bounds of a resizable window
+    pub min_size: Option<(u32, u32)>,
+    pub max_size: Option<(u32, u32)>,
+    pub transparent: bool,
+    pub unfocused_fps: Option<u32>,
+    // See `GraphicsContextSettings::max_frame_latency`
+    pub max_frame_latency: u32,
+    // Letterboxes the rendered frame to keep this width over height ratio, cursor positions are
+    // reported relative to the letterboxed viewport
+    pub maintain_aspect: Option<f32>,
+    // See `GraphicsContextSettings::surface_format`
+    pub surface_format: Option<wgpu::TextureFormat>,
+    // Fixed updates per second, see `ApplicationHandler::fixed_update`
+    pub fixed_update_rate: Option<u32>,
+    // Appends the frame rate to the title once per second, the title is left as is otherwise
+    pub show_fps_in_title: bool,
+    // Pressing this key queues `ApplicationCommand::Reload`, the key event is still dispatched
+    pub reload_key: Option<KeyCode>,
+    // Follows the touch events of the first finger with cursor and left button events, see `TouchMouseEmulation`
+    pub touch_as_mouse: bool,
+}
+
+impl Default for ApplicationSettings {
+    fn default() -> Self {
+        Self {
+            title: "navagfx-engine".to_owned(),
+            width: 800,
+            height: 600,
+            resizable: true,
+            min_size: None,
+            max_size: None,
+            transparent: false,
+            unfocused_fps: Some(10),
+            max_frame_latency: 2,
+            maintain_aspect: None,
+            surface_format: None,
+            fixed_update_rate: None,
+            show_fps_in_title: false,
+            reload_key: None,
+            touch_as_mouse: false,
+        }
+    }
+}
+
+impl ApplicationSettings {
+    fn window_attributes(&self) -> WindowAttributes {
+        self.check_size_bounds();
+
+        let mut attributes = WindowAttributes::default()
+            .with_title(self.title.clone())
+            .with_inner_size(LogicalSize::new(self.width, self.height))
+            .with_resizable(self.resizable)
+            .with_transparent(self.transparent);
+
+        if let Some((width, height)) = self.min_size {
+            attributes = attributes.with_min_inner_size(LogicalSize::new(width, height));
+        }
+
+        if let Some((width, height)) = self.max_size {
+            attributes = attributes.with_max_inner_size(LogicalSize::new(width, height));
+        }
+
+        attributes
+    }
+
+    fn check_size_bounds(&self) {
+        let size = (self.width, self.height);
+
+        if let (Some(min), Some(max)) = (self.min_size, self.max_size) && (min.0 > max.0 || min.1 > max.1) {
+            log::warn!("Window minimum size {}x{} is larger than the maximum size {}x{}", min.0, min.1, max.0, max.1);
+        }
+
+        if let Some(min) = self.min_size && (size.0 < min.0 || size.1 < min.1) {
+            log::warn!("Window size {}x{} is smaller than the minimum size {}x{}", size.0, size.1, min.0, min.1);
+        }
+
+        if let Some(max) = self.max_size && (size.0 > max.0 || size.1 > max.1) {
+            log::warn!("Window size {}x{} is larger than the maximum size {}x{}", size.0, size.1, max.0, max.1);
+        }
+    }
+
+    fn unfocused_frame_duration(&self) -> Option<Duration> {
+        self.unfocused_fps
+            .filter(|fps| *fps > 0)
+            .map(|fps| Duration::from_secs_f64(1.0 / fps as f64))
+    }
+
+    fn fixed_step(&self) -> Option<Duration> {
+        self.fixed_update_rate
+            .filter(|rate| *rate > 0)
+            .map(|rate| Duration::from_secs_f64(1.0 / rate as f64))
+    }
+
+    fn is_reload_key(&self, event: &ApplicationEvent) -> bool {
+        match (self.reload_key, event) {
+            (Some(reload_key), ApplicationEvent::KeyPressed { key_info, repeat: false }) => key_info.is_key_code(reload_key),
+            _ => false,
+        }
+    }
+
+    fn graphics_context_settings(&self) -> GraphicsContextSettings {
+        GraphicsContextSettings {
+            transparent: self.transparent,
+            max_frame_latency: self.max_frame_latency,
+            maintain_aspect: self.maintain_aspect,
+            surface_format: self.surface_format,
+        }
+    }
 }
 
 
@@ -18,35 +189,221 @@ pub struct Application<Handler: ApplicationHandler> {
     handler: Option<Handler>,
     data: Option<AppData>,
     timer: Timer,
+    frame_timer: Timer,
+    settings: ApplicationSettings,
+    window_settings: Vec<ApplicationSettings>,
+    focused: bool,
+    fixed_accumulator: Duration,
+    frame_time: FrameTime,
+    commands: CommandBuffer,
+    layers: LayerStack,
+    touch_emulation: TouchMouseEmulation,
+
+    input_recorder: Option<InputRecorderRef>,
+    input_player: Option<InputPlayer>,
+}
+
+impl<Handler: ApplicationHandler> Default for Application<Handler> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<Handler: ApplicationHandler> Application<Handler> {
     pub fn new() -> Self {
+        Self::with_settings(Default::default())
+    }
+
+    pub fn with_settings(settings: ApplicationSettings) -> Self {
 
         Self {
             handler: None,
             data: None,
             timer: Timer::new(),
+            frame_timer: Timer::new(),
+            settings,
+            window_settings: Vec::new(),
+            focused: true,
+            fixed_accumulator: Duration::ZERO,
+            frame_time: FrameTime::default(),
+            commands: CommandBuffer::new(),
+            layers: LayerStack::new(),
+            touch_emulation: TouchMouseEmulation::new(),
+
+            input_recorder: None,
+            input_player: None,
         }
     }
 
+    pub fn with_input_recorder(mut self, recorder: InputRecorderRef) -> Self {
+        self.input_recorder = Some(recorder);
+        self
+    }
+
+    pub fn with_event_layer(mut self, layer: impl EventLayer + 'static) -> Self {
+        self.layers.push(layer);
+        self
+    }
+
+    // Only the window attributes and the aspect ratio are used, secondary windows are redrawn along
+    // with the main window and closing them does not exit the application
+    pub fn with_window(mut self, settings: ApplicationSettings) -> Self {
+        self.window_settings.push(settings);
+        self
+    }
+
+    // Live input events are ignored while a player is attached
+    pub fn with_input_player(mut self, player: InputPlayer) -> Self {
+        self.input_player = Some(player);
+        self
+    }
+
     pub fn run(mut self) {
         log::info!("Application is running ...");
 
+        engine_time();
+
         let event_loop = EventLoop::with_user_event().build().unwrap();
 
-        event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
+        event_loop.set_control_flow(ControlFlow::Poll);
 
         event_loop.run_app(&mut self).unwrap();
     }
 
 
-    fn handle_signal(&self, event_loop: &ActiveEventLoop, signal: ApplicationSignal) {
+    fn dispatch_event(
+        handler: &mut Handler,
+        recorder: Option<&InputRecorderRef>,
+        layers: &mut LayerStack,
+        commands: &mut CommandBuffer,
+        event: ApplicationEvent,
+        dt: f32
+    ) -> ApplicationSignal {
+        let timestamp = engine_time();
+
+        if let Some(recorder) = recorder {
+            recorder.lock().unwrap().record(&event);
+        }
+
+        if layers.dispatch(&event, dt, commands).is_consumed() {
+            return ApplicationSignal::Continue;
+        }
+
+        handler.handle_timed_event(TimedEvent { timestamp, event }, dt, commands)
+    }
+
+    fn execute_commands(handler: &mut Handler, commands: &mut CommandBuffer, event_loop: &ActiveEventLoop, data: &mut AppData) {
+        for command in commands.drain() {
+            match command {
+                ApplicationCommand::SetTitle(title) => {
+                    data.window.set_title(&title);
+                    data.title = title;
+                }
+                ApplicationCommand::SetFullscreen(fullscreen) => {
+                    data.window.set_fullscreen(fullscreen.then_some(Fullscreen::Borderless(None)));
+                }
+                ApplicationCommand::Screenshot(path) => data.context.request_screenshot(path),
+                ApplicationCommand::Reload => {
+                    log::info!("Reloading assets");
+                    data.assets_manager.lock().unwrap().reload_textures(&data.context);
+                    handler.on_reload(&data.context);
+                }
+                ApplicationCommand::Quit => event_loop.exit(),
+            }
+        }
+    }
+
+    // Runs the fixed steps due and returns the interpolation between the last two
+    fn run_fixed_updates(&mut self, event_loop: &ActiveEventLoop) -> f32 {
+        // Steps beyond this are dropped so a slow frame does not make the next ones slower
+        const MAX_STEPS_PER_FRAME: u32 = 8;
+
+        let Some(step) = self.settings.fixed_step() else {
+            return 1.0;
+        };
+
+        let handler = self.handler.as_mut().unwrap();
+        let mut steps = 0;
+
+        while self.fixed_accumulator >= step {
+            if steps == MAX_STEPS_PER_FRAME {
+                log::warn!("Fixed update is running late, skipping {:?}", self.fixed_accumulator);
+                self.fixed_accumulator = Duration::ZERO;
+                break;
+            }
+
+            let signal = handler.fixed_update(step.as_secs_f32(), &mut self.commands);
+            Self::handle_signal(event_loop, signal);
+
+            self.fixed_accumulator -= step;
+            steps += 1;
+        }
+
+        (self.fixed_accumulator.as_secs_f64() / step.as_secs_f64()) as f32
+    }
+
+    fn handle_signal(event_loop: &ActiveEventLoop, signal: ApplicationSignal) {
         match signal {
             ApplicationSignal::Exit => event_loop.exit(),
             ApplicationSignal::Continue => (),
         }
     }
+
+    fn secondary_window_event(&mut self, event_loop: &ActiveEventLoop, position: usize, event: WindowEvent) {
+        let data = self.data.as_mut().unwrap();
+        let handler = self.handler.as_mut().unwrap();
+        let commands = &mut self.commands;
+
+        let secondary = &mut data.secondary_windows[position];
+        let index = secondary.index;
+        let dt = self.timer.elapsed().as_secs_f32();
+
+        let signal = match event {
+            WindowEvent::CloseRequested => {
+                log::info!("Closing window {index}");
+                data.secondary_windows.remove(position);
+                None
+            }
+            WindowEvent::RedrawRequested => {
+                match handler.draw_window(index, &secondary.context) {
+                    Ok(()) => (),
+                    Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+                        secondary.context.resize(secondary.context.config.width, secondary.context.config.height);
+                    }
+
+                    Err(e) => log::error!("Error while drawing to window {index} surface {e:?}"),
+                }
+
+                None
+            }
+
+            WindowEvent::Resized(size) => {
+                secondary.context.resize(size.width, size.height);
+
+                let event = ApplicationEvent::Resized { width: size.width, height: size.height };
+                Some(handler.handle_window_event(index, event, dt, commands))
+            }
+
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                let size = secondary.window.inner_size();
+
+                secondary.context.set_scale_factor(scale_factor);
+                secondary.context.resize(size.width, size.height);
+
+                let event = ApplicationEvent::ScaleFactorChanged { scale_factor };
+                Some(handler.handle_window_event(index, event, dt, commands))
+            }
+
+            ev => ApplicationEvent::from_window_event(ev)
+                .map(|app_event| to_viewport_event(&secondary.context, app_event))
+                .map(|app_event| handler.handle_window_event(index, app_event, dt, commands))
+        };
+        Self::execute_commands(handler, commands, event_loop, data);
+
+        if let Some(signal) = signal {
+            Self::handle_signal(event_loop, signal);
+        }
+    }
 }
 
 
@@ -54,93 +411,300 @@ impl<Handler: ApplicationHandler> winit::application::ApplicationHandler<AppData
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
         log::info!("Initializing application data and handler");
         
-        let window = event_loop.create_window(WindowAttributes::default()).unwrap();
-        let data = smol::block_on(AppData::new(window));
+        let window = event_loop.create_window(self.settings.window_attributes()).unwrap();
+        let mut data = smol::block_on(AppData::new(window, &self.settings));
+
+        for (index, settings) in self.window_settings.iter().enumerate() {
+            let window = event_loop.create_window(settings.window_attributes()).unwrap();
+            data.secondary_windows.push(SecondaryWindow::new(index, window, &data.context, settings.maintain_aspect));
+        }
 
         self.handler = Some(Handler::init(&data.context, data.assets_manager.clone()));
 
         self.data = Some(data);
 
         self.timer.restart();
+        self.frame_timer.restart();
+    }
+
+    fn new_events(&mut self, _event_loop: &ActiveEventLoop, cause: StartCause) {
+        if let (StartCause::ResumeTimeReached { .. }, Some(data)) = (cause, self.data.as_ref()) {
+            data.window.request_redraw();
+        }
     }
 
     fn window_event(
         &mut self,
         event_loop: &winit::event_loop::ActiveEventLoop,
-        _window_id: winit::window::WindowId,
+        window_id: winit::window::WindowId,
         event: winit::event::WindowEvent,
     ) {
 
-        let data = self.data.as_mut().unwrap();
-        let handler = self.handler.as_mut().unwrap();
+        let secondary_window = self.data.as_ref().unwrap().secondary_windows.iter()
+            .position(|window| window.window.id() == window_id);
+
+        if let Some(position) = secondary_window {
+            self.secondary_window_event(event_loop, position, event);
+            return;
+        }
 
         let elapsed = self.timer.restart();
         let elapsed_as_secs = elapsed.as_secs_f32();
 
-        
+        self.fixed_accumulator += elapsed;
+
+        let interpolation = if matches!(event, WindowEvent::RedrawRequested) {
+            self.run_fixed_updates(event_loop)
+        }
+        else {
+            1.0
+        };
+
+        let data = self.data.as_mut().unwrap();
+        let handler = self.handler.as_mut().unwrap();
+        let recorder = self.input_recorder.as_ref();
+        let commands = &mut self.commands;
+        let layers = &mut self.layers;
+
+        let consumed = !matches!(event, WindowEvent::RedrawRequested) && handler.handle_raw_event(&event);
+
+        #[cfg(feature = "egui")]
+        let ui_consumed = !consumed && data.egui_state.on_window_event(&data.window, &event).consumed;
+        #[cfg(not(feature = "egui"))]
+        let ui_consumed = false;
+
         let signal = match event {
-            WindowEvent::CloseRequested => {event_loop.exit(); None}
+            WindowEvent::CloseRequested if !consumed => {event_loop.exit(); None}
             WindowEvent::RedrawRequested => {
 
-                let signal = handler.update(elapsed_as_secs);
+                if let Some(player) = self.input_player.as_mut() {
+                    for event in player.poll() {
+                        let signal = Self::dispatch_event(handler, recorder, layers, commands, event, elapsed_as_secs);
+                        Self::handle_signal(event_loop, signal);
+                    }
+                }
+
+                data.assets_manager.lock().unwrap().upload_decoded_textures(&data.context);
+
+                // The delta covers the time since the previous frame, the events `dt` is the time since
+                // the previous event
+                self.frame_time.advance(self.frame_timer.restart());
 
-                match handler.draw(&data.context) {
+                let signal = handler.update_timed(self.frame_time, commands);
+                Self::execute_commands(handler, commands, event_loop, data);
+
+                #[cfg(feature = "egui")]
+                data.run_egui(handler);
+
+                match handler.draw_interpolated(&data.context, interpolation) {
                     Ok(()) => (),
                     Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
-                        data.context.resize_surface(data.context.config.width, data.context.config.height);
+                        data.context.resize(data.context.config.width, data.context.config.height);
                     }
 
                     Err(e) => log::error!("Error while drawing to surface {e:?}"),
                 }
 
-                data.window.request_redraw();
+                if self.settings.show_fps_in_title {
+                    data.count_frame();
+                }
+
+                for secondary in &data.secondary_windows {
+                    secondary.window.request_redraw();
+                }
+
+                match self.settings.unfocused_frame_duration().filter(|_| !self.focused) {
+                    Some(frame_duration) => event_loop.set_control_flow(ControlFlow::WaitUntil(Instant::now() + frame_duration)),
+                    None => data.window.request_redraw(),
+                }
 
                 Some(signal)
             }
 
             WindowEvent::Resized(size) => {
-                data.context.resize_surface(size.width, size.height);
+                data.context.resize(size.width, size.height);
 
-                Some(handler.handle_event(ApplicationEvent::Resized { width: size.width, height: size.height }, elapsed_as_secs))
+                let event = ApplicationEvent::Resized { width: size.width, height: size.height };
+                (!consumed).then(|| Self::dispatch_event(handler, recorder, layers, commands, event, elapsed_as_secs))
             }
 
-            ev => if let Some(app_event) = ApplicationEvent::from_window_event(ev) {
-                Some(handler.handle_event(app_event, elapsed_as_secs))
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                let size = data.window.inner_size();
+
+                data.context.set_scale_factor(scale_factor);
+                data.context.resize(size.width, size.height);
+
+                let event = ApplicationEvent::ScaleFactorChanged { scale_factor };
+                (!consumed).then(|| Self::dispatch_event(handler, recorder, layers, commands, event, elapsed_as_secs))
             }
-            else {
-                None
+
+            WindowEvent::Focused(focused) => {
+                self.focused = focused;
+
+                if focused {
+                    event_loop.set_control_flow(ControlFlow::Poll);
+                    data.window.request_redraw();
+                }
+
+                (!consumed).then(|| Self::dispatch_event(handler, recorder, layers, commands, ApplicationEvent::Focused(focused), elapsed_as_secs))
             }
+
+            _ if consumed => None,
+
+            ev => ApplicationEvent::from_window_event(ev)
+                .map(|app_event| to_viewport_event(&data.context, app_event))
+                .filter(|app_event| !app_event.is_input() || (self.input_player.is_none() && !ui_consumed))
+                .inspect(|app_event| if self.settings.is_reload_key(app_event) {
+                    commands.reload();
+                })
+                .map(|app_event| {
+                    let mouse_events = if self.settings.touch_as_mouse {
+                        self.touch_emulation.mouse_events(&app_event)
+                    }
+                    else {
+                        vec![]
+                    };
+
+                    let signal = Self::dispatch_event(handler, recorder, layers, commands, app_event, elapsed_as_secs);
+
+                    for event in mouse_events {
+                        let signal = Self::dispatch_event(handler, recorder, layers, commands, event, elapsed_as_secs);
+                        Self::handle_signal(event_loop, signal);
+                    }
+
+                    signal
+                })
         };
+        Self::execute_commands(handler, commands, event_loop, data);
+
         if let Some(signal) = signal {
-            self.handle_signal(event_loop, signal);
+            Self::handle_signal(event_loop, signal);
         }
 
     }
 }
 
+// Cursor and touch positions are reported relative to the letterboxed viewport
+fn to_viewport_event(context: &GraphicsContext, event: ApplicationEvent) -> ApplicationEvent {
+    match event {
+        ApplicationEvent::CursorMoved { x, y } => {
+            let position = context.surface_to_viewport(glam::vec2(x, y));
+            ApplicationEvent::CursorMoved { x: position.x, y: position.y }
+        }
+        ApplicationEvent::Touch { id, phase, x, y } => {
+            let position = context.surface_to_viewport(glam::vec2(x, y));
+            ApplicationEvent::Touch { id, phase, x: position.x, y: position.y }
+        }
+        event => event,
+    }
+}
+
 struct AppData {
     window: Arc<Window>,
     context: GraphicsContext<'static>,
-    assets_manager: AssetsManagerRef
+    assets_manager: AssetsManagerRef,
+    secondary_windows: Vec<SecondaryWindow>,
+
+    title: String,
+    fps_frames: u32,
+    fps_timer: Timer,
+
+    #[cfg(feature = "egui")]
+    egui_state: egui_winit::State,
 }
 
 impl AppData {
-    async fn new(window: Window) -> Self {
+    async fn new(window: Window, settings: &ApplicationSettings) -> Self {
         log::info!("init app data");
         let window = Arc::new(window);
 
         let size = window.inner_size();
 
-        let context = GraphicsContext::new(window.clone(), size.width, size.height).await;
+        let mut context = GraphicsContext::with_settings(window.clone(), size.width, size.height, &settings.graphics_context_settings()).await;
+        context.set_scale_factor(window.scale_factor());
 
         let assets_manager = AssetsManager::new()
             .register_assets_type::<Texture2D>()
             ;
 
+        #[cfg(feature = "egui")]
+        let egui_state = egui_winit::State::new(
+            egui::Context::default(),
+            egui::ViewportId::ROOT,
+            &*window,
+            Some(window.scale_factor() as f32),
+            window.theme(),
+            Some(context.device.limits().max_texture_dimension_2d as usize)
+        );
+
         Self {
             window,
             context,
-            assets_manager: Arc::new(Mutex::new(assets_manager))
+            assets_manager: Arc::new(Mutex::new(assets_manager)),
+            secondary_windows: Vec::new(),
+
+            title: settings.title.clone(),
+            fps_frames: 0,
+            fps_timer: Timer::new(),
+
+            #[cfg(feature = "egui")]
+            egui_state,
         }
     }
-}
\ No newline at end of file
+
+    fn count_frame(&mut self) {
+        self.fps_frames += 1;
+
+        let elapsed = self.fps_timer.elapsed();
+
+        if elapsed >= Duration::from_secs(1) {
+            let fps = self.fps_frames as f64 / elapsed.as_secs_f64();
+            self.window.set_title(&format!("{} - {fps:.0} FPS", self.title));
+
+            self.fps_frames = 0;
+            self.fps_timer.restart();
+        }
+    }
+
+    #[cfg(feature = "egui")]
+    fn run_egui<Handler: ApplicationHandler>(&mut self, handler: &mut Handler) {
+        let raw_input = self.egui_state.take_egui_input(&self.window);
+        let egui_ctx = self.egui_state.egui_ctx().clone();
+
+        let full_output = egui_ctx.run(raw_input, |ctx| handler.run_ui(ctx));
+
+        self.egui_state.handle_platform_output(&self.window, full_output.platform_output);
+
+        let pixels_per_point = full_output.pixels_per_point;
+
+        self.context.set_egui_frame(crate::graphics::egui_painter::EguiFrame {
+            primitives: egui_ctx.tessellate(full_output.shapes, pixels_per_point),
+            textures_delta: full_output.textures_delta,
+            pixels_per_point,
+        });
+    }
+}
+
+struct SecondaryWindow {
+    index: usize,
+    window: Arc<Window>,
+    context: GraphicsContext<'static>,
+}
+
+impl SecondaryWindow {
+    fn new(index: usize, window: Window, main_context: &GraphicsContext, maintain_aspect: Option<f32>) -> Self {
+        let window = Arc::new(window);
+
+        let size = window.inner_size();
+
+        let mut context = main_context.share_device(window.clone(), size.width, size.height);
+        context.set_scale_factor(window.scale_factor());
+        context.set_maintain_aspect(maintain_aspect);
+
+        Self {
+            index,
+            window,
+            context,
+        }
+    }
+}
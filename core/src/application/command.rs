@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+
+#[derive(Debug, Clone)]
+pub enum ApplicationCommand {
+    SetTitle(String),
+    SetFullscreen(bool),
+    Screenshot(PathBuf),
+    // Reloads the textures loaded from files then calls `ApplicationHandler::on_reload`
+    Reload,
+    Quit,
+}
+
+#[derive(Debug, Default)]
+pub struct CommandBuffer {
+    commands: Vec<ApplicationCommand>,
+}
+
+impl CommandBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, command: ApplicationCommand) {
+        self.commands.push(command);
+    }
+
+    pub fn set_title(&mut self, title: impl Into<String>) {
+        self.push(ApplicationCommand::SetTitle(title.into()));
+    }
+
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.push(ApplicationCommand::SetFullscreen(fullscreen));
+    }
+
+    pub fn screenshot(&mut self, path: impl Into<PathBuf>) {
+        self.push(ApplicationCommand::Screenshot(path.into()));
+    }
+
+    pub fn reload(&mut self) {
+        self.push(ApplicationCommand::Reload);
+    }
+
+    pub fn quit(&mut self) {
+        self.push(ApplicationCommand::Quit);
+    }
+
+    pub(crate) fn drain(&mut self) -> impl Iterator<Item = ApplicationCommand> + '_ {
+        self.commands.drain(..)
+    }
+}
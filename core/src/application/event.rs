@@ -1,18 +1,36 @@
-use winit::{event::{ElementState, KeyEvent, WindowEvent}, keyboard::{Key, KeyCode, PhysicalKey}};
+use std::time::Duration;
 
+use winit::{event::{ElementState, KeyEvent, MouseButton, Touch, TouchPhase, WindowEvent}, keyboard::{Key, KeyCode, PhysicalKey}};
 
-#[derive(Debug)]
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ApplicationEvent {
+    // Physical size of the window
     Resized {width: u32, height: u32},
+    Focused(bool),
+    ScaleFactorChanged {scale_factor: f64},
 
     KeyPressed {key_info: KeyInfo, repeat: bool},
     KeyReleased(KeyInfo),
+
+    // Physical pixels from the top left corner of the window
+    CursorMoved {x: f32, y: f32},
+    MouseButtonPressed(MouseButton),
+    MouseButtonReleased(MouseButton),
+
+    // One event per finger, the id stays the same from `Started` to `Ended` or `Cancelled`.
+    // Physical pixels like the cursor
+    Touch {id: u64, phase: TouchPhase, x: f32, y: f32},
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KeyInfo {
+    // Position of the key on the keyboard, independent of the layout
     pub physical_key_code: KeyCode,
+    // Character produced by the key with the current layout and modifiers
     pub symbol: Option<char>,
 }
 
@@ -24,6 +42,20 @@ impl KeyInfo {
         }
     }
 
+    // `None` for keys winit could not identify
+    pub(crate) fn from_keys(physical_key: PhysicalKey, logical_key: &Key) -> Option<Self> {
+        let PhysicalKey::Code(key) = physical_key else {
+            return None;
+        };
+
+        let symbol = match logical_key {
+            Key::Character(sym_str) => sym_str.chars().next(),
+            _ => None,
+        };
+
+        Some(Self::new(key, symbol))
+    }
+
     pub fn is_key_code(&self, key_code: KeyCode) -> bool {
         self.physical_key_code == key_code
     }
@@ -35,15 +67,28 @@ impl KeyInfo {
 
 
 impl ApplicationEvent {
+    pub fn is_input(&self) -> bool {
+        matches!(
+            self,
+            Self::KeyPressed { .. } | Self::KeyReleased(_)
+            | Self::CursorMoved { .. } | Self::MouseButtonPressed(_) | Self::MouseButtonReleased(_)
+            | Self::Touch { .. }
+        )
+    }
+
     pub fn from_window_event(event: WindowEvent) -> Option<Self> {
         match event {
             WindowEvent::Resized(size) => {
                 Some(Self::Resized { width: size.width, height: size.height })
             }
 
+            WindowEvent::Focused(focused) => Some(Self::Focused(focused)),
+
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => Some(Self::ScaleFactorChanged { scale_factor }),
+
             WindowEvent::KeyboardInput {
                 event: KeyEvent {
-                    physical_key: PhysicalKey::Code(key),
+                    physical_key,
                     logical_key,
                     state,
                     repeat,
@@ -52,12 +97,7 @@ impl ApplicationEvent {
                 ..
             } => {
 
-                let key_info = if let Key::Character(sym_str) = logical_key {
-                    KeyInfo::new(key, Some(sym_str.chars().next().unwrap()))
-                }
-                else {
-                    KeyInfo::new(key, None)
-                };
+                let key_info = KeyInfo::from_keys(physical_key, &logical_key)?;
 
                 let ev = match state {
                     ElementState::Pressed => Self::KeyPressed { key_info, repeat},
@@ -67,13 +107,89 @@ impl ApplicationEvent {
                 Some(ev)
             }
 
+            WindowEvent::CursorMoved { position, .. } => Some(Self::CursorMoved { x: position.x as f32, y: position.y as f32 }),
+
+            WindowEvent::MouseInput { state, button, .. } => match state {
+                ElementState::Pressed => Some(Self::MouseButtonPressed(button)),
+                ElementState::Released => Some(Self::MouseButtonReleased(button)),
+            }
+
+            WindowEvent::Touch(Touch { id, phase, location, .. }) => {
+                Some(Self::Touch { id, phase, x: location.x as f32, y: location.y as f32 })
+            }
+
             _ => None
         }
     }
 }
 
 
+// The timestamp is the `engine_time` the event was received at, on the same clock as the frame `dt`
+#[derive(Debug, Clone)]
+pub struct TimedEvent {
+    pub timestamp: Duration,
+    pub event: ApplicationEvent,
+}
+
+// Frame timing kept as `Duration` so the total time does not drift in long sessions like an f32
+// accumulation would, use the f64 seconds for animations driven by the total time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameTime {
+    pub delta: Duration,
+    // Sum of the frame deltas since the application started
+    pub total: Duration,
+    pub frame: u64,
+}
+
+impl FrameTime {
+    pub fn dt(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    pub fn dt_f64(&self) -> f64 {
+        self.delta.as_secs_f64()
+    }
+
+    pub fn total_seconds(&self) -> f64 {
+        self.total.as_secs_f64()
+    }
+
+    pub(crate) fn advance(&mut self, delta: Duration) {
+        self.delta = delta;
+        self.total += delta;
+        self.frame += 1;
+    }
+}
+
+
 pub enum ApplicationSignal {
     Exit,
     Continue,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::Timer;
+
+    #[test]
+    fn frame_time_total_follows_wall_clock() {
+        let start = Instant::now();
+        let mut timer = Timer::new();
+        let mut frame_time = FrameTime::default();
+
+        for _ in 0..5000 {
+            std::hint::black_box((0..100).sum::<u64>());
+
+            frame_time.advance(timer.restart());
+        }
+
+        let wall = start.elapsed();
+
+        assert_eq!(frame_time.frame, 5000);
+        assert!(frame_time.total <= wall);
+        assert!(wall - frame_time.total < Duration::from_millis(1), "{:?} drifted from {wall:?}", frame_time.total);
+    }
+}
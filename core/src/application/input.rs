@@ -0,0 +1,356 @@
+use std::{collections::{HashMap, HashSet}, time::Duration};
+
+use winit::{event::{MouseButton, TouchPhase}, keyboard::KeyCode};
+
+use crate::{application::event::ApplicationEvent, engine_time};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyState {
+    Pressed,
+    Released,
+}
+
+// Key pressed while `KeyboardInput` captures, the symbol is the character the key produced with the
+// current layout and is meant for display ("A" rather than KeyA)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapturedKey {
+    pub key: KeyCode,
+    pub symbol: Option<char>,
+}
+
+// Keys are tracked by physical position (`KeyCode::KeyW` is the key at the W position of a QWERTY
+// keyboard, Z on AZERTY) which suits movement bindings, use the char queries for text shortcuts
+#[derive(Debug, Default)]
+pub struct KeyboardInput {
+    keys_state: HashMap<KeyCode, KeyState>,
+    // `engine_time` of the press
+    pressed_at: HashMap<KeyCode, Duration>,
+    pressed_symbols: HashMap<KeyCode, char>,
+    capturing: bool,
+    captured: Option<CapturedKey>,
+}
+
+impl KeyboardInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn handle_event(&mut self, event: &ApplicationEvent) {
+        match event {
+            ApplicationEvent::KeyPressed { key_info, repeat: false } if self.capturing => {
+                self.capturing = false;
+                self.captured = Some(CapturedKey {
+                    key: key_info.physical_key_code,
+                    symbol: key_info.symbol,
+                });
+            }
+
+            ApplicationEvent::KeyPressed { key_info, .. } => {
+                let key = key_info.physical_key_code;
+
+                self.keys_state.insert(key, KeyState::Pressed);
+                self.pressed_at.entry(key).or_insert_with(engine_time);
+
+                if let Some(symbol) = key_info.symbol {
+                    self.pressed_symbols.insert(key, symbol);
+                }
+            }
+
+            ApplicationEvent::KeyReleased(key_info) => {
+                let key = key_info.physical_key_code;
+
+                self.keys_state.insert(key, KeyState::Released);
+                self.pressed_at.remove(&key);
+                self.pressed_symbols.remove(&key);
+            }
+
+            _ => ()
+        }
+    }
+
+    pub fn key_state(&self, key: KeyCode) -> KeyState {
+        self.keys_state.get(&key).copied().unwrap_or(KeyState::Released)
+    }
+
+    pub fn is_key_pressed(&self, key: KeyCode) -> bool {
+        self.key_state(key) == KeyState::Pressed
+    }
+
+    // Layout dependent, matches the character the key produced when it was pressed
+    pub fn is_char_pressed(&self, symbol: char) -> bool {
+        self.pressed_symbols.values().any(|pressed| *pressed == symbol)
+    }
+
+    pub fn pressed_keys(&self) -> impl Iterator<Item = KeyCode> + '_ {
+        self.keys_state.iter()
+        .filter(|(_, state)| **state == KeyState::Pressed)
+        .map(|(key, _)| *key)
+    }
+
+    pub fn held_duration(&self, key: KeyCode) -> Option<Duration> {
+        self.pressed_at.get(&key).map(|pressed_at| engine_time().saturating_sub(*pressed_at))
+    }
+
+    // The next key press is recorded for `take_captured_key` instead of updating the key states,
+    // for rebinding screens. Keys already held keep repeating and are not captured
+    pub fn capture_next_key(&mut self) {
+        self.capturing = true;
+        self.captured = None;
+    }
+
+    pub fn cancel_capture(&mut self) {
+        self.capturing = false;
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.capturing
+    }
+
+    pub fn take_captured_key(&mut self) -> Option<CapturedKey> {
+        self.captured.take()
+    }
+}
+
+
+// Tracks the cursor and buttons from the application events, the just pressed and released
+// buttons are kept until `end_frame` which is meant to be called once per update
+#[derive(Debug, Default)]
+pub struct MouseInput {
+    position: glam::Vec2,
+    frame_start_position: glam::Vec2,
+    motion_samples: Vec<glam::Vec2>,
+    held: HashSet<MouseButton>,
+    press_positions: HashMap<MouseButton, glam::Vec2>,
+    just_pressed: HashSet<MouseButton>,
+    just_released: HashSet<MouseButton>,
+}
+
+impl MouseInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn handle_event(&mut self, event: &ApplicationEvent) {
+        match event {
+            ApplicationEvent::CursorMoved { x, y } => {
+                self.position = glam::vec2(*x, *y);
+                self.motion_samples.push(self.position);
+            }
+
+            ApplicationEvent::MouseButtonPressed(button) => {
+                self.held.insert(*button);
+                self.press_positions.insert(*button, self.position);
+                self.just_pressed.insert(*button);
+            }
+
+            ApplicationEvent::MouseButtonReleased(button) => {
+                self.held.remove(button);
+                self.just_released.insert(*button);
+            }
+
+            _ => ()
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        self.just_pressed.clear();
+        self.just_released.clear();
+        self.press_positions.retain(|button, _| self.held.contains(button));
+        self.motion_samples.clear();
+        self.frame_start_position = self.position;
+    }
+
+    // Physical pixels, see `ApplicationEvent::CursorMoved`
+    pub fn position(&self) -> glam::Vec2 {
+        self.position
+    }
+
+    // Every cursor position received since the last `end_frame` in order, the last one is `position`.
+    // High poll rate mice report several per frame, drawing tools should connect all of them
+    pub fn motion_samples(&self) -> &[glam::Vec2] {
+        &self.motion_samples
+    }
+
+    // Cursor movement since the last `end_frame`
+    pub fn motion_delta(&self) -> glam::Vec2 {
+        self.position - self.frame_start_position
+    }
+
+    pub fn is_button_pressed(&self, button: MouseButton) -> bool {
+        self.held.contains(&button)
+    }
+
+    pub fn is_button_just_pressed(&self, button: MouseButton) -> bool {
+        self.just_pressed.contains(&button)
+    }
+
+    pub fn is_button_just_released(&self, button: MouseButton) -> bool {
+        self.just_released.contains(&button)
+    }
+
+    // Where the cursor was when the button was pressed, available while the button is held and
+    // until the end of the frame it is released in, tells drags and clicks apart from hovers
+    pub fn press_position(&self, button: MouseButton) -> Option<glam::Vec2> {
+        self.press_positions.get(&button).copied()
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TouchPoint {
+    pub id: u64,
+    pub position: glam::Vec2,
+    pub start_position: glam::Vec2,
+}
+
+// Tracks the fingers on the screen, like `MouseInput` the started and ended touches are kept until `end_frame`
+#[derive(Debug, Default)]
+pub struct TouchInput {
+    // In the order the fingers touched the screen
+    touches: Vec<TouchPoint>,
+    just_started: Vec<u64>,
+    just_ended: Vec<TouchPoint>,
+}
+
+impl TouchInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn handle_event(&mut self, event: &ApplicationEvent) {
+        let ApplicationEvent::Touch { id, phase, x, y } = *event else {
+            return;
+        };
+
+        let position = glam::vec2(x, y);
+
+        match phase {
+            TouchPhase::Started => {
+                self.touches.retain(|touch| touch.id != id);
+                self.touches.push(TouchPoint { id, position, start_position: position });
+                self.just_started.push(id);
+            }
+
+            TouchPhase::Moved => {
+                if let Some(touch) = self.touches.iter_mut().find(|touch| touch.id == id) {
+                    touch.position = position;
+                }
+            }
+
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                if let Some(index) = self.touches.iter().position(|touch| touch.id == id) {
+                    let mut touch = self.touches.remove(index);
+                    touch.position = position;
+                    self.just_ended.push(touch);
+                }
+            }
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        self.just_started.clear();
+        self.just_ended.clear();
+    }
+
+    pub fn touches(&self) -> &[TouchPoint] {
+        &self.touches
+    }
+
+    pub fn touch(&self, id: u64) -> Option<&TouchPoint> {
+        self.touches.iter().find(|touch| touch.id == id)
+    }
+
+    // The oldest finger still on the screen
+    pub fn primary(&self) -> Option<&TouchPoint> {
+        self.touches.first()
+    }
+
+    pub fn just_started(&self) -> impl Iterator<Item = &TouchPoint> + '_ {
+        self.just_started.iter().filter_map(|id| self.touch(*id))
+    }
+
+    // Last position of the touches lifted or cancelled this frame
+    pub fn just_ended(&self) -> &[TouchPoint] {
+        &self.just_ended
+    }
+}
+
+// Turns the first finger touching the screen into cursor moves and left button presses so mouse
+// driven code works on touch screens, the other fingers are ignored until it is lifted
+#[derive(Debug, Default)]
+pub struct TouchMouseEmulation {
+    primary: Option<u64>,
+}
+
+impl TouchMouseEmulation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mouse_events(&mut self, event: &ApplicationEvent) -> Vec<ApplicationEvent> {
+        let ApplicationEvent::Touch { id, phase, x, y } = *event else {
+            return vec![];
+        };
+
+        let cursor_moved = ApplicationEvent::CursorMoved { x, y };
+
+        match phase {
+            TouchPhase::Started if self.primary.is_none() => {
+                self.primary = Some(id);
+                vec![cursor_moved, ApplicationEvent::MouseButtonPressed(MouseButton::Left)]
+            }
+
+            TouchPhase::Moved if self.primary == Some(id) => vec![cursor_moved],
+
+            TouchPhase::Ended | TouchPhase::Cancelled if self.primary == Some(id) => {
+                self.primary = None;
+                vec![cursor_moved, ApplicationEvent::MouseButtonReleased(MouseButton::Left)]
+            }
+
+            _ => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::keyboard::{Key, PhysicalKey};
+
+    use super::*;
+    use crate::application::event::KeyInfo;
+
+    fn key_pressed(physical_key: PhysicalKey, logical_key: Key) -> ApplicationEvent {
+        let key_info = KeyInfo::from_keys(physical_key, &logical_key).unwrap();
+        ApplicationEvent::KeyPressed { key_info, repeat: false }
+    }
+
+    #[test]
+    fn keys_follow_physical_position() {
+        let mut keyboard = KeyboardInput::new();
+
+        // W position of an AZERTY keyboard
+        keyboard.handle_event(&key_pressed(PhysicalKey::Code(KeyCode::KeyW), Key::Character("z".into())));
+
+        assert!(keyboard.is_key_pressed(KeyCode::KeyW));
+        assert!(!keyboard.is_key_pressed(KeyCode::KeyZ));
+        assert!(keyboard.is_char_pressed('z'));
+        assert!(!keyboard.is_char_pressed('w'));
+    }
+
+    #[test]
+    fn held_duration_is_kept_until_release() {
+        let mut keyboard = KeyboardInput::new();
+        let space = || KeyInfo::from_keys(PhysicalKey::Code(KeyCode::Space), &Key::Character(" ".into())).unwrap();
+
+        keyboard.handle_event(&ApplicationEvent::KeyPressed { key_info: space(), repeat: false });
+        std::thread::sleep(Duration::from_millis(5));
+
+        // Repeats keep the time of the first press
+        keyboard.handle_event(&ApplicationEvent::KeyPressed { key_info: space(), repeat: true });
+        assert!(keyboard.held_duration(KeyCode::Space).unwrap() >= Duration::from_millis(5));
+
+        keyboard.handle_event(&ApplicationEvent::KeyReleased(space()));
+        assert_eq!(keyboard.held_duration(KeyCode::Space), None);
+    }
+}
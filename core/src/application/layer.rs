@@ -0,0 +1,48 @@
+use crate::application::{command::CommandBuffer, event::ApplicationEvent};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventStatus {
+    Ignored,
+    Consumed,
+}
+
+impl EventStatus {
+    pub fn is_consumed(&self) -> bool {
+        matches!(self, Self::Consumed)
+    }
+}
+
+// Layers see every event before the handler, a consumed input event is not propagated
+// to the following layers nor to the handler. Non input events are never stopped
+pub trait EventLayer {
+    fn handle_event(&mut self, event: &ApplicationEvent, dt: f32, commands: &mut CommandBuffer) -> EventStatus;
+}
+
+#[derive(Default)]
+pub struct LayerStack {
+    layers: Vec<Box<dyn EventLayer>>,
+}
+
+impl LayerStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Layers are dispatched in insertion order, push the top most one (e.g. UI) first
+    pub fn push(&mut self, layer: impl EventLayer + 'static) {
+        self.layers.push(Box::new(layer));
+    }
+
+    pub fn dispatch(&mut self, event: &ApplicationEvent, dt: f32, commands: &mut CommandBuffer) -> EventStatus {
+        for layer in self.layers.iter_mut() {
+            let status = layer.handle_event(event, dt, commands);
+
+            if status.is_consumed() && event.is_input() {
+                return EventStatus::Consumed;
+            }
+        }
+
+        EventStatus::Ignored
+    }
+}
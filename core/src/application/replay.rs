@@ -0,0 +1,91 @@
+use std::{sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use crate::application::event::ApplicationEvent;
+
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RecordedEvent {
+    pub timestamp: Duration,
+    pub event: ApplicationEvent,
+}
+
+pub type InputRecorderRef = Arc<Mutex<InputRecorder>>;
+
+#[derive(Debug)]
+pub struct InputRecorder {
+    start: Instant,
+    events: Vec<RecordedEvent>,
+}
+
+impl Default for InputRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputRecorder {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.start = Instant::now();
+        self.events.clear();
+    }
+
+    pub fn record(&mut self, event: &ApplicationEvent) {
+        self.events.push(RecordedEvent {
+            timestamp: self.start.elapsed(),
+            event: event.clone(),
+        });
+    }
+
+    pub fn events(&self) -> &[RecordedEvent] {
+        &self.events
+    }
+
+    pub fn take_events(&mut self) -> Vec<RecordedEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+#[derive(Debug)]
+pub struct InputPlayer {
+    events: Vec<RecordedEvent>,
+    next_event: usize,
+    start: Option<Instant>,
+}
+
+impl InputPlayer {
+    pub fn new(events: Vec<RecordedEvent>) -> Self {
+        Self {
+            events,
+            next_event: 0,
+            start: None,
+        }
+    }
+
+    // Returns the recorded input events that are due since the first call to poll
+    pub fn poll(&mut self) -> impl Iterator<Item = ApplicationEvent> + '_ {
+        let elapsed = self.start.get_or_insert_with(Instant::now).elapsed();
+
+        let first = self.next_event;
+        let due = self.events[first..].iter()
+            .take_while(|recorded| recorded.timestamp <= elapsed)
+            .count();
+
+        self.next_event += due;
+
+        self.events[first..first + due].iter()
+            .filter(|recorded| recorded.event.is_input())
+            .map(|recorded| recorded.event.clone())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_event >= self.events.len()
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{application::{command::CommandBuffer, event::{ApplicationEvent, ApplicationSignal}}, graphics::GraphicsContext};
+
+
+pub enum StateTransition<T> {
+    None,
+    Push(Box<dyn AppState<T>>),
+    Pop,
+    Replace(Box<dyn AppState<T>>),
+    // Pops every state, the stack then asks the application to exit
+    Clear,
+}
+
+// Game screen such as a title, level or game over screen. `T` is the data shared by the states,
+// usually the renderer and the assets owned by the handler
+pub trait AppState<T> {
+    fn update(&mut self, shared: &mut T, dt: f32, commands: &mut CommandBuffer) -> StateTransition<T>;
+    fn draw(&mut self, shared: &mut T, context: &GraphicsContext) -> Result<(), wgpu::SurfaceError>;
+
+    fn handle_event(&mut self, _shared: &mut T, _event: &ApplicationEvent, _dt: f32, _commands: &mut CommandBuffer) -> StateTransition<T> {
+        StateTransition::None
+    }
+
+    // Called when the state becomes active, after being pushed or when the state above it is popped
+    fn on_enter(&mut self, _shared: &mut T) {}
+
+    // Called when the state stops being active, before being popped or when a state is pushed above it
+    fn on_exit(&mut self, _shared: &mut T) {}
+}
+
+// Only the top state is updated, drawn and receives the events. The handler owns the stack and
+// forwards its callbacks, an empty stack returns `ApplicationSignal::Exit`
+pub struct StateStack<T> {
+    states: Vec<Box<dyn AppState<T>>>,
+}
+
+impl<T> Default for StateStack<T> {
+    fn default() -> Self {
+        Self { states: Vec::new() }
+    }
+}
+
+impl<T> StateStack<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, shared: &mut T, state: impl AppState<T> + 'static) {
+        self.apply(shared, StateTransition::Push(Box::new(state)));
+    }
+
+    pub fn pop(&mut self, shared: &mut T) {
+        self.apply(shared, StateTransition::Pop);
+    }
+
+    pub fn replace(&mut self, shared: &mut T, state: impl AppState<T> + 'static) {
+        self.apply(shared, StateTransition::Replace(Box::new(state)));
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    pub fn update(&mut self, shared: &mut T, dt: f32, commands: &mut CommandBuffer) -> ApplicationSignal {
+        if let Some(state) = self.states.last_mut() {
+            let transition = state.update(shared, dt, commands);
+            self.apply(shared, transition);
+        }
+
+        self.signal()
+    }
+
+    pub fn handle_event(&mut self, shared: &mut T, event: &ApplicationEvent, dt: f32, commands: &mut CommandBuffer) -> ApplicationSignal {
+        if let Some(state) = self.states.last_mut() {
+            let transition = state.handle_event(shared, event, dt, commands);
+            self.apply(shared, transition);
+        }
+
+        self.signal()
+    }
+
+    pub fn draw(&mut self, shared: &mut T, context: &GraphicsContext) -> Result<(), wgpu::SurfaceError> {
+        match self.states.last_mut() {
+            Some(state) => state.draw(shared, context),
+            None => Ok(()),
+        }
+    }
+
+    pub fn apply(&mut self, shared: &mut T, transition: StateTransition<T>) {
+        match transition {
+            StateTransition::None => (),
+            StateTransition::Push(mut state) => {
+                if let Some(active) = self.states.last_mut() {
+                    active.on_exit(shared);
+                }
+
+                state.on_enter(shared);
+                self.states.push(state);
+            }
+            StateTransition::Pop => {
+                if let Some(mut state) = self.states.pop() {
+                    state.on_exit(shared);
+                }
+
+                if let Some(active) = self.states.last_mut() {
+                    active.on_enter(shared);
+                }
+            }
+            StateTransition::Replace(mut state) => {
+                if let Some(mut previous) = self.states.pop() {
+                    previous.on_exit(shared);
+                }
+
+                state.on_enter(shared);
+                self.states.push(state);
+            }
+            StateTransition::Clear => {
+                // The states below the top one already exited when it was pushed
+                if let Some(mut state) = self.states.pop() {
+                    state.on_exit(shared);
+                }
+
+                self.states.clear();
+            }
+        }
+    }
+
+    fn signal(&self) -> ApplicationSignal {
+        if self.states.is_empty() { ApplicationSignal::Exit } else { ApplicationSignal::Continue }
+    }
+}
@@ -1,25 +1,115 @@
-use std::{any::{Any, TypeId}, collections::HashMap, fmt::Debug, hash::Hash, marker::PhantomData, sync::{Arc, Mutex}};
+use std::{any::{Any, TypeId}, cell::Cell, collections::{HashMap, HashSet}, fmt::{Debug, Display}, hash::Hash, marker::PhantomData, path::{Path, PathBuf}, sync::{Arc, Mutex}};
+
+use crate::{assets::{compressed::CompressedImageError, decode::{DecodePool, DecodedTexture}, texture::Texture2D}, graphics::GraphicsContext};
 
 
 pub mod texture;
+pub mod atlas;
+pub mod compressed;
+pub mod load_queue;
+mod decode;
 
 pub trait Asset {}
 
 pub type AssetsManagerRef = Arc<Mutex<AssetsManager>>;
 
+#[derive(Debug)]
+pub enum AssetsManagerError {
+    Io(std::io::Error),
+    Image(image::ImageError),
+    Compressed(CompressedImageError),
+    UnknownSource,
+}
+
+impl Display for AssetsManagerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "Failed to read asset: {err}"),
+            Self::Image(err) => write!(f, "Failed to decode image: {err}"),
+            Self::Compressed(err) => write!(f, "Failed to read compressed texture: {err}"),
+            Self::UnknownSource => write!(f, "The asset was not loaded from a file and cannot be reloaded"),
+        }
+    }
+}
+
+impl std::error::Error for AssetsManagerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Image(err) => Some(err),
+            Self::Compressed(err) => Some(err),
+            Self::UnknownSource => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for AssetsManagerError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<CompressedImageError> for AssetsManagerError {
+    fn from(err: CompressedImageError) -> Self {
+        Self::Compressed(err)
+    }
+}
+
+impl From<image::ImageError> for AssetsManagerError {
+    fn from(err: image::ImageError) -> Self {
+        Self::Image(err)
+    }
+}
+
 pub struct AssetsManager {
-    storages: HashMap<TypeId, Box<dyn Any>>
+    // `Send` so the manager can be shared through `AssetsManagerRef`, an `Arc<Mutex>` of a non `Send`
+    // manager could never leave its thread
+    storages: HashMap<TypeId, Box<dyn Any + Send>>,
+
+    texture_sizes: HashMap<u32, u64>,
+    texture_paths: HashMap<u32, PathBuf>,
+    texture_ids: HashMap<PathBuf, u32>,
+    texture_bytes: u64,
+    texture_budget: Option<u64>,
+
+    decode_workers: usize,
+    decode_pool: Option<DecodePool>,
+    pending_textures: HashSet<u32>,
 }
 
 
+impl Default for AssetsManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AssetsManager {
     pub fn new() -> Self {
         Self {
-            storages: HashMap::new()
+            storages: HashMap::new(),
+
+            texture_sizes: HashMap::new(),
+            texture_paths: HashMap::new(),
+            texture_ids: HashMap::new(),
+            texture_bytes: 0,
+            texture_budget: None,
+
+            decode_workers: 2,
+            decode_pool: None,
+            pending_textures: HashSet::new(),
         }
     }
 
-    pub fn register_assets_type<TAsset: 'static>(mut self) -> Self {
+    // Threads decoding the files of `load_texture_async`, started with the first asynchronous load
+    pub fn with_worker_threads(mut self, worker_count: usize) -> Self {
+        self.decode_workers = worker_count.max(1);
+        self.decode_pool = None;
+        self
+    }
+
+    // Asset types must be `Send`, see `storages`
+    pub fn register_assets_type<TAsset: Send + 'static>(mut self) -> Self {
         let asset_type_id = TypeId::of::<TAsset>();
 
         let old_storage = self.storages.insert(asset_type_id, Box::new(AssetsStorage::<TAsset>::new()));
@@ -32,30 +122,303 @@ impl AssetsManager {
     }
 
     pub fn store_asset<TAsset: 'static>(&mut self, asset: TAsset) -> AssetHandle<TAsset> {
-        self.get_storage_mut().store_asset(asset)
+        let texture_size = (&asset as &dyn Any).downcast_ref::<Texture2D>().map(Texture2D::size_in_bytes);
+        let handle = self.get_storage_mut().store_asset(asset);
+
+        if let Some(size) = texture_size {
+            self.account_texture(handle.id, size);
+        }
+
+        handle
     }
 
+    // Panics if the asset was evicted, see `try_get_asset`
     pub fn get_asset<TAsset: 'static>(&self, handle: AssetHandle<TAsset>) -> &TAsset {
         self.get_storage().get_asset(handle)
     }
 
+    pub fn try_get_asset<TAsset: 'static>(&self, handle: AssetHandle<TAsset>) -> Option<&TAsset> {
+        self.get_storage().try_get_asset(handle)
+    }
 
+    // Textures keep the size accounted when they were stored, replacing one through this reference
+    // does not update the budget
+    pub fn get_asset_mut<TAsset: 'static>(&mut self, handle: AssetHandle<TAsset>) -> Option<&mut TAsset> {
+        self.get_storage_mut().get_asset_mut(handle)
+    }
+
+    pub fn is_loaded<TAsset: 'static>(&self, handle: AssetHandle<TAsset>) -> bool {
+        self.get_storage::<TAsset>().storage.contains_key(&handle.id)
+    }
+
+    // Evicts the least recently used textures loaded from a file while their total size is over
+    // the budget, the handles stay valid and `reload_texture` brings them back
+    pub fn set_texture_budget(&mut self, budget_bytes: Option<u64>) {
+        self.texture_budget = budget_bytes;
+        self.evict_textures(None);
+    }
+
+    pub fn texture_bytes(&self) -> u64 {
+        self.texture_bytes
+    }
+
+    pub fn reload_texture(&mut self, context: &GraphicsContext, handle: AssetHandle<Texture2D>) -> Result<(), AssetsManagerError> {
+        let path = self.texture_paths.get(&handle.id).ok_or(AssetsManagerError::UnknownSource)?;
+
+        log::info!("Reloading texture {}", path.display());
+
+        let texture = Self::read_texture(context, path)?;
+        let size = texture.size_in_bytes();
+
+        self.get_storage_mut().insert_asset(handle, texture);
+        self.account_texture(handle.id, size);
+
+        Ok(())
+    }
+
+    // Reads every loaded texture again from its file, evicted textures are read when next drawn
+    pub fn reload_textures(&mut self, context: &GraphicsContext) {
+        let handles = self.texture_paths.keys()
+            .map(|id| AssetHandle::<Texture2D>::new(*id))
+            .filter(|handle| self.is_loaded(*handle))
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            if let Err(err) = self.reload_texture(context, handle) {
+                log::error!("Failed to reload texture {handle:?}: {err}");
+            }
+        }
+    }
+
+    pub fn handles<TAsset: 'static>(&self) -> Vec<AssetHandle<TAsset>> {
+        self.get_storage::<TAsset>().storage.keys()
+            .map(|id| AssetHandle::new(*id))
+            .collect()
+    }
+
+    pub fn for_each_asset<TAsset: 'static>(&self, mut f: impl FnMut(AssetHandle<TAsset>, &TAsset)) {
+        self.get_storage::<TAsset>().storage.iter()
+            .for_each(|(id, asset)| f(AssetHandle::new(*id), asset));
+    }
+
+    // The file is decoded on a worker thread and uploaded by `upload_decoded_textures`, until then
+    // the handle is pending and not loaded. Failures are logged and the handle stays unloaded
+    pub fn load_texture_async(&mut self, path: impl AsRef<Path>) -> AssetHandle<Texture2D> {
+        let path = path.as_ref();
+        let handle = self.get_storage_mut::<Texture2D>().reserve_handle();
+
+        self.texture_paths.insert(handle.id, path.to_owned());
+        self.texture_ids.insert(path.to_owned(), handle.id);
+        self.pending_textures.insert(handle.id);
+
+        self.decode_pool
+            .get_or_insert_with(|| DecodePool::new(self.decode_workers))
+            .submit(handle.id, path.to_owned());
+
+        handle
+    }
+
+    pub fn is_pending(&self, handle: AssetHandle<Texture2D>) -> bool {
+        self.pending_textures.contains(&handle.id)
+    }
+
+    // Creates the textures decoded since the last call, the application calls it once per frame.
+    // Returns the number of textures uploaded
+    pub fn upload_decoded_textures(&mut self, context: &GraphicsContext) -> usize {
+        let Some(pool) = &self.decode_pool else {
+            return 0;
+        };
+
+        let finished = pool.finished().collect::<Vec<_>>();
+        let mut uploaded = 0;
+
+        for (id, decoded) in finished {
+            self.pending_textures.remove(&id);
+
+            let path = self.texture_paths[&id].clone();
+            let handle = AssetHandle::new(id);
+
+            match decoded.and_then(|decoded| Self::upload_texture(context, &path, decoded)) {
+                Ok(texture) => {
+                    let size = texture.size_in_bytes();
+
+                    self.get_storage_mut().insert_asset(handle, texture);
+                    self.account_texture(id, size);
+                    uploaded += 1;
+                }
+                Err(err) => log::error!("Failed to load texture {}: {err}", path.display()),
+            }
+        }
+
+        uploaded
+    }
+
+    pub fn load_texture(&mut self, context: &GraphicsContext, path: impl AsRef<Path>) -> Result<AssetHandle<Texture2D>, AssetsManagerError> {
+        let path = path.as_ref();
+        let texture = Self::read_texture(context, path)?;
+
+        let id = self.get_storage::<Texture2D>().next_id;
+        self.texture_paths.insert(id, path.to_owned());
+        self.texture_ids.insert(path.to_owned(), id);
+
+        Ok(self.store_asset(texture))
+    }
+
+    // Handle ids change between runs, the source path is what should be saved to find a texture again
+    pub fn texture_path(&self, handle: AssetHandle<Texture2D>) -> Option<&Path> {
+        self.texture_paths.get(&handle.id).map(PathBuf::as_path)
+    }
+
+    // The handle of the last texture loaded from the path
+    pub fn texture_by_path(&self, path: impl AsRef<Path>) -> Option<AssetHandle<Texture2D>> {
+        self.texture_ids.get(path.as_ref()).map(|id| AssetHandle::new(*id))
+    }
+
+    // Reuses the texture loaded from the path if any, used to resolve the handles of a saved scene
+    pub fn resolve_texture(&mut self, context: &GraphicsContext, path: impl AsRef<Path>) -> Result<AssetHandle<Texture2D>, AssetsManagerError> {
+        match self.texture_by_path(&path) {
+            Some(handle) => Ok(handle),
+            None => self.load_texture(context, path),
+        }
+    }
+
+    // Loads the files matching the glob pattern (`*` and `?` wildcards) sorted by name,
+    // files that fail to load are reported and skipped
+    pub fn load_directory(&mut self, context: &GraphicsContext, dir: &str, glob: &str) -> Result<Vec<AssetHandle<Texture2D>>, AssetsManagerError> {
+        let mut paths = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .filter(|path| path.file_name().is_some_and(|name| matches_glob(glob, &name.to_string_lossy())))
+            .collect::<Vec<_>>();
+
+        paths.sort();
+
+        let handles = paths.iter()
+            .filter_map(|path| match self.load_texture(context, path) {
+                Ok(handle) => Some(handle),
+                Err(err) => {
+                    log::warn!("Failed to load texture {}: {err}", path.display());
+                    None
+                }
+            })
+            .collect();
+
+        Ok(handles)
+    }
+
+
+
+    fn read_texture(context: &GraphicsContext, path: &Path) -> Result<Texture2D, AssetsManagerError> {
+        Self::upload_texture(context, path, DecodedTexture::read(path)?)
+    }
+
+    // DDS and KTX2 files are uploaded compressed, when the device lacks their format a PNG
+    // with the same name is loaded instead
+    fn upload_texture(context: &GraphicsContext, path: &Path, decoded: DecodedTexture) -> Result<Texture2D, AssetsManagerError> {
+        let label = path.to_string_lossy();
+
+        let image = match decoded {
+            DecodedTexture::Rgba(image) => return Ok(Texture2D::from_image(context, &label, &image)),
+            DecodedTexture::Compressed(image) => image,
+        };
+
+        let required_features = image.format.required_features();
+
+        if context.device.features().contains(required_features) {
+            return Ok(Texture2D::from_compressed(context, &label, &image));
+        }
+
+        let fallback = path.with_extension("png");
+
+        log::warn!(
+            "Texture format {:?} of {} is not supported by the device, loading {} instead",
+            image.format,
+            path.display(),
+            fallback.display()
+        );
+
+        let image = image::open(&fallback)?.to_rgba8();
+        Ok(Texture2D::from_image(context, &label, &image))
+    }
+
+    fn account_texture(&mut self, id: u32, size: u64) {
+        self.texture_bytes += size;
+
+        if let Some(old_size) = self.texture_sizes.insert(id, size) {
+            self.texture_bytes -= old_size;
+        }
+
+        self.evict_textures(Some(id));
+    }
+
+    fn evict_textures(&mut self, keep: Option<u32>) {
+        let Some(budget) = self.texture_budget else {
+            return;
+        };
+
+        while self.texture_bytes > budget {
+            let storage = self.get_storage::<Texture2D>();
+            let evictable = storage.least_recently_used(|id| Some(id) != keep && self.texture_paths.contains_key(&id));
+
+            let Some(id) = evictable else {
+                log::warn!("Textures use {} bytes over the {budget} bytes budget but none can be evicted", self.texture_bytes);
+                return;
+            };
+
+            log::info!("Evicting texture {}", self.texture_paths[&id].display());
+
+            self.get_storage_mut::<Texture2D>().storage.remove(&id);
+            self.texture_bytes -= self.texture_sizes.remove(&id).unwrap_or(0);
+        }
+    }
 
     fn get_storage_mut<TAsset: 'static>(&mut self) -> &mut AssetsStorage<TAsset> {
         self.storages.get_mut(&TypeId::of::<TAsset>())
         .and_then(|s| s.downcast_mut::<AssetsStorage<TAsset>>())
-        .expect(&format!("No storage created for type {}", std::any::type_name::<TAsset>()))
+        .unwrap_or_else(|| panic!("No storage created for type {}", std::any::type_name::<TAsset>()))
     }
 
     fn get_storage<TAsset: 'static>(&self) -> &AssetsStorage<TAsset> {
         self.storages.get(&TypeId::of::<TAsset>())
         .and_then(|s| s.downcast_ref::<AssetsStorage<TAsset>>())
-        .expect(&format!("No storage created for type {}", std::any::type_name::<TAsset>()))
+        .unwrap_or_else(|| panic!("No storage created for type {}", std::any::type_name::<TAsset>()))
     }
 
 }
 
 
+fn matches_glob(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    backtrack = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            }
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
 pub struct AssetHandle<T> {
     id: u32,
     _marker: PhantomData<T>
@@ -63,7 +426,7 @@ pub struct AssetHandle<T> {
 
 impl<T> Clone for AssetHandle<T> {
     fn clone(&self) -> Self {
-        Self { id: self.id.clone(), _marker: self._marker.clone() }
+        *self
     }
 }
 impl<T> Copy for AssetHandle<T> {}
@@ -96,11 +459,26 @@ impl<T> AssetHandle<T> {
             _marker: PhantomData
         }
     }
+
+    // Only unique within a run, see `AssetsManager::texture_path` for an id that can be saved
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    // The id must come from `id` in the same run, a handle to an unknown id panics in `get_asset`
+    #[doc(hidden)]
+    pub fn from_id(id: u32) -> Self {
+        Self::new(id)
+    }
 }
 
+// Ids are never reused so a handle to an evicted asset can not alias another one
 struct AssetsStorage<T> {
     next_id: u32,
     storage: HashMap<u32, T>,
+
+    last_used: HashMap<u32, Cell<u64>>,
+    clock: Cell<u64>,
 }
 
 impl<T> AssetsStorage<T> {
@@ -108,19 +486,61 @@ impl<T> AssetsStorage<T> {
         Self {
             next_id: 0,
             storage: HashMap::new(),
+
+            last_used: HashMap::new(),
+            clock: Cell::new(0),
         }
     }
 
     fn store_asset(&mut self, asset: T) -> AssetHandle<T> {
-        let handle = self.next_id;
+        let handle = self.reserve_handle();
+        self.insert_asset(handle, asset);
+
+        handle
+    }
 
-        self.storage.insert(handle, asset);
+    // A handle to an asset inserted later
+    fn reserve_handle(&mut self) -> AssetHandle<T> {
+        let handle = AssetHandle::new(self.next_id);
         self.next_id += 1;
 
-        AssetHandle::new(handle)
+        handle
+    }
+
+    fn insert_asset(&mut self, handle: AssetHandle<T>, asset: T) {
+        self.storage.insert(handle.id, asset);
+        self.last_used.insert(handle.id, Cell::new(0));
+        self.touch(handle.id);
     }
 
     fn get_asset(&self, handle: AssetHandle<T>) -> &T {
-        self.storage.get(&handle.id).unwrap()
+        self.try_get_asset(handle).unwrap()
+    }
+
+    fn try_get_asset(&self, handle: AssetHandle<T>) -> Option<&T> {
+        let asset = self.storage.get(&handle.id)?;
+        self.touch(handle.id);
+
+        Some(asset)
+    }
+
+    fn get_asset_mut(&mut self, handle: AssetHandle<T>) -> Option<&mut T> {
+        self.touch(handle.id);
+        self.storage.get_mut(&handle.id)
+    }
+
+    fn touch(&self, id: u32) {
+        self.clock.set(self.clock.get() + 1);
+
+        if let Some(last_used) = self.last_used.get(&id) {
+            last_used.set(self.clock.get());
+        }
+    }
+
+    fn least_recently_used(&self, filter: impl Fn(u32) -> bool) -> Option<u32> {
+        self.storage.keys()
+            .copied()
+            .filter(|id| filter(*id))
+            .min_by_key(|id| self.last_used[id].get())
     }
 }
\ No newline at end of file
@@ -0,0 +1,179 @@
+use std::{collections::HashMap, fmt::Display, path::Path};
+
+use image::RgbaImage;
+
+use crate::{assets::texture::{Texture2D, Texture2DCoordinates}, graphics::GraphicsContext};
+
+
+#[derive(Debug)]
+pub enum AtlasError {
+    Image(image::ImageError),
+    DoesNotFit { max_size: u32 },
+}
+
+impl Display for AtlasError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Image(err) => write!(f, "Failed to load atlas image: {err}"),
+            Self::DoesNotFit { max_size } => write!(f, "Images do not fit in a {max_size}x{max_size} atlas"),
+        }
+    }
+}
+
+impl std::error::Error for AtlasError {}
+
+impl From<image::ImageError> for AtlasError {
+    fn from(err: image::ImageError) -> Self {
+        Self::Image(err)
+    }
+}
+
+pub struct TextureAtlas {
+    pub texture: Texture2D,
+    pub coords: HashMap<String, Texture2DCoordinates>,
+}
+
+impl TextureAtlas {
+    pub fn get_coords(&self, name: &str) -> Option<Texture2DCoordinates> {
+        self.coords.get(name).copied()
+    }
+}
+
+pub struct PackedAtlas {
+    pub image: RgbaImage,
+    pub coords: HashMap<String, Texture2DCoordinates>,
+}
+
+pub struct AtlasBuilder {
+    images: Vec<(String, RgbaImage)>,
+    padding: u32,
+    max_size: u32,
+}
+
+impl Default for AtlasBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Self {
+            images: Vec::new(),
+            padding: 1,
+            max_size: 8192,
+        }
+    }
+
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn with_max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    pub fn add_image(mut self, name: impl Into<String>, image: RgbaImage) -> Self {
+        self.images.push((name.into(), image));
+        self
+    }
+
+    // The file name is used as the image name
+    pub fn add_image_file(self, path: impl AsRef<Path>) -> Result<Self, AtlasError> {
+        let path = path.as_ref();
+        let image = image::open(path)?.to_rgba8();
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+
+        Ok(self.add_image(name, image))
+    }
+
+    pub fn build(self, context: &GraphicsContext, label: &str) -> Result<TextureAtlas, AtlasError> {
+        let max_size = self.max_size.min(context.device.limits().max_texture_dimension_2d);
+
+        let packed = self.with_max_size(max_size).pack()?;
+
+        Ok(TextureAtlas {
+            texture: Texture2D::from_image(context, label, &packed.image),
+            coords: packed.coords,
+        })
+    }
+
+    pub fn pack(mut self) -> Result<PackedAtlas, AtlasError> {
+        self.images.sort_by_key(|(_, image)| std::cmp::Reverse(image.height()));
+
+        let total_area: u64 = self.images.iter()
+            .map(|(_, image)| (image.width() + self.padding) as u64 * (image.height() + self.padding) as u64)
+            .sum();
+
+        let widest = self.images.iter()
+            .map(|(_, image)| image.width() + self.padding)
+            .max()
+            .unwrap_or(1);
+
+        let mut size = widest.max((total_area as f64).sqrt().ceil() as u32).next_power_of_two();
+
+        let positions = loop {
+            if size > self.max_size {
+                return Err(AtlasError::DoesNotFit { max_size: self.max_size });
+            }
+
+            match self.shelf_pack(size) {
+                Some(positions) => break positions,
+                None => size *= 2,
+            }
+        };
+
+        let mut atlas_image = RgbaImage::new(size, size);
+        let mut coords = HashMap::new();
+
+        for ((name, image), (x, y)) in self.images.iter().zip(positions) {
+            image::imageops::replace(&mut atlas_image, image, x as i64, y as i64);
+
+            coords.insert(name.clone(), Texture2DCoordinates {
+                size: [image.width() as f32 / size as f32, image.height() as f32 / size as f32],
+                offset: [x as f32 / size as f32, y as f32 / size as f32],
+            });
+        }
+
+        Ok(PackedAtlas {
+            image: atlas_image,
+            coords,
+        })
+    }
+
+    fn shelf_pack(&self, size: u32) -> Option<Vec<(u32, u32)>> {
+        let mut positions = Vec::with_capacity(self.images.len());
+
+        let mut shelf_x = 0;
+        let mut shelf_y = 0;
+        let mut shelf_height = 0;
+
+        for (_, image) in &self.images {
+            let width = image.width() + self.padding;
+            let height = image.height() + self.padding;
+
+            if width > size {
+                return None;
+            }
+
+            if shelf_x + width > size {
+                shelf_y += shelf_height;
+                shelf_x = 0;
+                shelf_height = 0;
+            }
+
+            if shelf_y + height > size {
+                return None;
+            }
+
+            positions.push((shelf_x, shelf_y));
+
+            shelf_x += width;
+            shelf_height = shelf_height.max(height);
+        }
+
+        Some(positions)
+    }
+}
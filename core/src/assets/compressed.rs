@@ -0,0 +1,195 @@
+use std::fmt::Display;
+
+
+// Block compressed mip chain read from a DDS or KTX2 file, the blocks are uploaded as is
+#[derive(Debug, Clone)]
+pub struct CompressedImage {
+    pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    // Largest level first
+    pub levels: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompressedImageError {
+    UnknownContainer,
+    Truncated,
+    UnsupportedFormat(String),
+    Supercompressed(u32),
+}
+
+impl Display for CompressedImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownContainer => write!(f, "Not a DDS or KTX2 file"),
+            Self::Truncated => write!(f, "The file is shorter than its header describes"),
+            Self::UnsupportedFormat(format) => write!(f, "Unsupported texture format {format}"),
+            Self::Supercompressed(scheme) => write!(f, "KTX2 supercompression scheme {scheme} is not supported"),
+        }
+    }
+}
+
+impl std::error::Error for CompressedImageError {}
+
+const DDS_MAGIC: &[u8] = b"DDS ";
+const KTX2_IDENTIFIER: &[u8] = &[0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+
+impl CompressedImage {
+    pub fn parse(bytes: &[u8]) -> Result<Self, CompressedImageError> {
+        if bytes.starts_with(DDS_MAGIC) {
+            Self::parse_dds(bytes)
+        }
+        else if bytes.starts_with(KTX2_IDENTIFIER) {
+            Self::parse_ktx2(bytes)
+        }
+        else {
+            Err(CompressedImageError::UnknownContainer)
+        }
+    }
+
+    pub fn is_compressed_path(path: &std::path::Path) -> bool {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("dds") || extension.eq_ignore_ascii_case("ktx2"))
+    }
+
+    fn parse_dds(bytes: &[u8]) -> Result<Self, CompressedImageError> {
+        const HEADER_SIZE: usize = 128;
+        const DX10_HEADER_SIZE: usize = 20;
+
+        let height = read_u32(bytes, 12)?;
+        let width = read_u32(bytes, 16)?;
+        let level_count = read_u32(bytes, 28)?.max(1);
+        let four_cc = bytes.get(84..88).ok_or(CompressedImageError::Truncated)?;
+
+        let (format, data_offset) = match four_cc {
+            b"DX10" => (dxgi_format(read_u32(bytes, HEADER_SIZE)?)?, HEADER_SIZE + DX10_HEADER_SIZE),
+            b"DXT1" => (wgpu::TextureFormat::Bc1RgbaUnorm, HEADER_SIZE),
+            b"DXT3" => (wgpu::TextureFormat::Bc2RgbaUnorm, HEADER_SIZE),
+            b"DXT5" => (wgpu::TextureFormat::Bc3RgbaUnorm, HEADER_SIZE),
+            b"ATI1" | b"BC4U" => (wgpu::TextureFormat::Bc4RUnorm, HEADER_SIZE),
+            b"ATI2" | b"BC5U" => (wgpu::TextureFormat::Bc5RgUnorm, HEADER_SIZE),
+            other => return Err(CompressedImageError::UnsupportedFormat(format!("DDS {}", String::from_utf8_lossy(other)))),
+        };
+
+        let mut offset = data_offset;
+        let mut levels = Vec::with_capacity(level_count as usize);
+
+        for level in 0..level_count {
+            let size = level_size(format, width, height, level);
+            levels.push(bytes.get(offset..offset + size).ok_or(CompressedImageError::Truncated)?.to_vec());
+            offset += size;
+        }
+
+        Ok(Self { format, width, height, levels })
+    }
+
+    fn parse_ktx2(bytes: &[u8]) -> Result<Self, CompressedImageError> {
+        const LEVEL_INDEX_OFFSET: usize = 80;
+
+        let vk_format = read_u32(bytes, 12)?;
+        let width = read_u32(bytes, 20)?;
+        let height = read_u32(bytes, 24)?.max(1);
+        let level_count = read_u32(bytes, 40)?.max(1);
+        let supercompression = read_u32(bytes, 44)?;
+
+        if supercompression != 0 {
+            return Err(CompressedImageError::Supercompressed(supercompression));
+        }
+
+        let format = vk_format_to_wgpu(vk_format)?;
+
+        let levels = (0..level_count)
+            .map(|level| {
+                let entry = LEVEL_INDEX_OFFSET + level as usize * 24;
+                let offset = read_u64(bytes, entry)? as usize;
+                let length = read_u64(bytes, entry + 8)? as usize;
+
+                // Levels are padded, only the blocks of the level are kept
+                let length = length.min(level_size(format, width, height, level));
+
+                Ok(bytes.get(offset..offset + length).ok_or(CompressedImageError::Truncated)?.to_vec())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { format, width, height, levels })
+    }
+}
+
+// Bytes of a tightly packed mip level
+pub(crate) fn level_size(format: wgpu::TextureFormat, width: u32, height: u32, level: u32) -> usize {
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format.block_copy_size(None).unwrap_or(4);
+
+    let level_width = (width >> level).max(1);
+    let level_height = (height >> level).max(1);
+
+    level_width.div_ceil(block_width) as usize * level_height.div_ceil(block_height) as usize * block_size as usize
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, CompressedImageError> {
+    bytes.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or(CompressedImageError::Truncated)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, CompressedImageError> {
+    Ok(read_u32(bytes, offset)? as u64 | (read_u32(bytes, offset + 4)? as u64) << 32)
+}
+
+fn dxgi_format(dxgi_format: u32) -> Result<wgpu::TextureFormat, CompressedImageError> {
+    use wgpu::TextureFormat as F;
+
+    Ok(match dxgi_format {
+        28 => F::Rgba8Unorm,
+        29 => F::Rgba8UnormSrgb,
+        71 => F::Bc1RgbaUnorm,
+        72 => F::Bc1RgbaUnormSrgb,
+        74 => F::Bc2RgbaUnorm,
+        75 => F::Bc2RgbaUnormSrgb,
+        77 => F::Bc3RgbaUnorm,
+        78 => F::Bc3RgbaUnormSrgb,
+        80 => F::Bc4RUnorm,
+        81 => F::Bc4RSnorm,
+        83 => F::Bc5RgUnorm,
+        84 => F::Bc5RgSnorm,
+        95 => F::Bc6hRgbUfloat,
+        96 => F::Bc6hRgbFloat,
+        98 => F::Bc7RgbaUnorm,
+        99 => F::Bc7RgbaUnormSrgb,
+        other => return Err(CompressedImageError::UnsupportedFormat(format!("DXGI {other}"))),
+    })
+}
+
+fn vk_format_to_wgpu(vk_format: u32) -> Result<wgpu::TextureFormat, CompressedImageError> {
+    use wgpu::{AstcBlock, AstcChannel, TextureFormat as F};
+
+    Ok(match vk_format {
+        37 => F::Rgba8Unorm,
+        43 => F::Rgba8UnormSrgb,
+        133 => F::Bc1RgbaUnorm,
+        134 => F::Bc1RgbaUnormSrgb,
+        135 => F::Bc2RgbaUnorm,
+        136 => F::Bc2RgbaUnormSrgb,
+        137 => F::Bc3RgbaUnorm,
+        138 => F::Bc3RgbaUnormSrgb,
+        139 => F::Bc4RUnorm,
+        140 => F::Bc4RSnorm,
+        141 => F::Bc5RgUnorm,
+        142 => F::Bc5RgSnorm,
+        143 => F::Bc6hRgbUfloat,
+        144 => F::Bc6hRgbFloat,
+        145 => F::Bc7RgbaUnorm,
+        146 => F::Bc7RgbaUnormSrgb,
+        147 => F::Etc2Rgb8Unorm,
+        148 => F::Etc2Rgb8UnormSrgb,
+        149 => F::Etc2Rgb8A1Unorm,
+        150 => F::Etc2Rgb8A1UnormSrgb,
+        151 => F::Etc2Rgba8Unorm,
+        152 => F::Etc2Rgba8UnormSrgb,
+        157 => F::Astc { block: AstcBlock::B4x4, channel: AstcChannel::Unorm },
+        158 => F::Astc { block: AstcBlock::B4x4, channel: AstcChannel::UnormSrgb },
+        other => return Err(CompressedImageError::UnsupportedFormat(format!("Vulkan {other}"))),
+    })
+}
@@ -0,0 +1,77 @@
+use std::{path::{Path, PathBuf}, sync::{mpsc, Arc, Mutex}};
+
+use image::RgbaImage;
+
+use crate::assets::{compressed::CompressedImage, AssetsManagerError};
+
+
+// CPU side of a texture, decoding needs no device so it can happen on a worker thread
+pub(crate) enum DecodedTexture {
+    Rgba(RgbaImage),
+    Compressed(CompressedImage),
+}
+
+impl DecodedTexture {
+    pub(crate) fn read(path: &Path) -> Result<Self, AssetsManagerError> {
+        if CompressedImage::is_compressed_path(path) {
+            Ok(Self::Compressed(CompressedImage::parse(&std::fs::read(path)?)?))
+        }
+        else {
+            Ok(Self::Rgba(image::open(path)?.to_rgba8()))
+        }
+    }
+}
+
+type DecodeJob = (u32, PathBuf);
+pub(crate) type DecodeResult = (u32, Result<DecodedTexture, AssetsManagerError>);
+
+// Worker threads decoding image files, the results are collected by the thread owning the device.
+// Dropping the pool closes both channels and the workers exit after their current job
+pub(crate) struct DecodePool {
+    jobs: mpsc::Sender<DecodeJob>,
+    results: mpsc::Receiver<DecodeResult>,
+}
+
+impl DecodePool {
+    pub(crate) fn new(worker_count: usize) -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<DecodeJob>();
+        let (result_sender, results) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        log::info!("Starting {worker_count} texture decode workers");
+
+        for index in 0..worker_count.max(1) {
+            let job_receiver = job_receiver.clone();
+            let result_sender = result_sender.clone();
+
+            std::thread::Builder::new()
+                .name(format!("navagfx texture decode {index}"))
+                .spawn(move || loop {
+                    // The lock is released before decoding so the other workers can take jobs
+                    let job = job_receiver.lock().unwrap().recv();
+
+                    let Ok((id, path)) = job else {
+                        break;
+                    };
+
+                    if result_sender.send((id, DecodedTexture::read(&path))).is_err() {
+                        break;
+                    }
+                })
+                .expect("Failed to spawn a texture decode worker");
+        }
+
+        Self {
+            jobs,
+            results,
+        }
+    }
+
+    pub(crate) fn submit(&self, id: u32, path: PathBuf) {
+        let _ = self.jobs.send((id, path));
+    }
+
+    pub(crate) fn finished(&self) -> impl Iterator<Item = DecodeResult> + '_ {
+        self.results.try_iter()
+    }
+}
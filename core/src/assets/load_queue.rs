@@ -0,0 +1,112 @@
+use std::path::{Path, PathBuf};
+
+use crate::assets::{texture::Texture2D, AssetHandle, AssetsManager};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadStatus {
+    Queued,
+    Loading,
+    Loaded,
+    Failed,
+}
+
+impl LoadStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Loaded | Self::Failed)
+    }
+}
+
+struct LoadItem {
+    path: PathBuf,
+    handle: Option<AssetHandle<Texture2D>>,
+    status: LoadStatus,
+}
+
+// Batch of textures loaded in the background, for loading screens. The files are decoded on the
+// assets manager workers and uploaded by the application before each update, `update` then
+// refreshes the state of the items. Failed items count as completed so the progress reaches 1
+#[derive(Default)]
+pub struct LoadQueue {
+    items: Vec<LoadItem>,
+}
+
+impl LoadQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns the index of the item, used by `status` and `handle`
+    pub fn add_texture(&mut self, path: impl AsRef<Path>) -> usize {
+        self.items.push(LoadItem {
+            path: path.as_ref().to_owned(),
+            handle: None,
+            status: LoadStatus::Queued,
+        });
+
+        self.items.len() - 1
+    }
+
+    // Sends the queued items to the decode workers, items added later need another call
+    pub fn start(&mut self, assets_manager: &mut AssetsManager) {
+        for item in self.items.iter_mut().filter(|item| item.status == LoadStatus::Queued) {
+            item.handle = Some(assets_manager.load_texture_async(&item.path));
+            item.status = LoadStatus::Loading;
+        }
+    }
+
+    // Returns the indices of the items finished since the last call
+    pub fn update(&mut self, assets_manager: &AssetsManager) -> Vec<usize> {
+        let mut finished = vec![];
+
+        for (index, item) in self.items.iter_mut().enumerate() {
+            let Some(handle) = item.handle.filter(|_| item.status == LoadStatus::Loading) else {
+                continue;
+            };
+
+            if assets_manager.is_pending(handle) {
+                continue;
+            }
+
+            item.status = if assets_manager.is_loaded(handle) { LoadStatus::Loaded } else { LoadStatus::Failed };
+            finished.push(index);
+        }
+
+        finished
+    }
+
+    pub fn status(&self, index: usize) -> Option<LoadStatus> {
+        self.items.get(index).map(|item| item.status)
+    }
+
+    // Available once the item is started, the texture can be drawn after it is loaded
+    pub fn handle(&self, index: usize) -> Option<AssetHandle<Texture2D>> {
+        self.items.get(index).and_then(|item| item.handle)
+    }
+
+    pub fn path(&self, index: usize) -> Option<&Path> {
+        self.items.get(index).map(|item| item.path.as_path())
+    }
+
+    // Finished items and total items
+    pub fn counts(&self) -> (usize, usize) {
+        let completed = self.items.iter().filter(|item| item.status.is_finished()).count();
+        (completed, self.items.len())
+    }
+
+    // Completed fraction in [0, 1], an empty queue is complete
+    pub fn progress(&self) -> f32 {
+        match self.counts() {
+            (_, 0) => 1.0,
+            (completed, total) => completed as f32 / total as f32,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.items.iter().all(|item| item.status.is_finished())
+    }
+
+    pub fn failed_count(&self) -> usize {
+        self.items.iter().filter(|item| item.status == LoadStatus::Failed).count()
+    }
+}
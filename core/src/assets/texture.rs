@@ -1,9 +1,9 @@
 use image::RgbaImage;
 
-use crate::{assets::Asset, graphics::GraphicsContext};
+use crate::{assets::{compressed::{self, CompressedImage}, Asset}, graphics::GraphicsContext};
 
 
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone)]
 pub struct Texture2DCoordinates {
     pub size: [f32; 2],
     pub offset: [f32; 2],
@@ -30,28 +30,53 @@ pub struct SpriteSheetCoordinates {
 }
 
 impl SpriteSheetCoordinates {
+    // Sprites crossing the texture border are dropped, use `with_partial_sprites` to keep them
     pub fn new(texture: &Texture2D, sprite_size: (u32, u32)) -> Self {
+        Self::build(texture, sprite_size, false)
+    }
+
+    // Keeps the last row and column when the texture size is not a multiple of the sprite size,
+    // their coordinates are clamped to the texture border
+    pub fn with_partial_sprites(texture: &Texture2D, sprite_size: (u32, u32)) -> Self {
+        Self::build(texture, sprite_size, true)
+    }
+
+    fn build(texture: &Texture2D, sprite_size: (u32, u32), include_partial: bool) -> Self {
         let (sprite_width, sprite_height) = sprite_size;
 
-        let size = [
-            sprite_width as f32 / texture.width as f32,
-            sprite_height as f32 / texture.height as f32,
-        ];
+        if !texture.width.is_multiple_of(sprite_width) || !texture.height.is_multiple_of(sprite_height) {
+            log::warn!(
+                "Texture size {}x{} is not a multiple of the sprite size {sprite_width}x{sprite_height}, partial sprites are {}",
+                texture.width,
+                texture.height,
+                if include_partial { "clamped" } else { "dropped" }
+            );
+        }
 
-        let mut atlas_coords = vec![];
+        let (rows, cols) = if include_partial {
+            (texture.height.div_ceil(sprite_height), texture.width.div_ceil(sprite_width))
+        }
+        else {
+            (texture.height / sprite_height, texture.width / sprite_width)
+        };
 
-        let rows = texture.height / sprite_height;
-        let cols = texture.width / sprite_width;
+        let mut atlas_coords = vec![];
 
         for y in 0..rows {
             for x in 0..cols {
-                let offset = [
-                    (x * sprite_width) as f32 / texture.width as f32,
-                    (y * sprite_height) as f32 / texture.height as f32
-                ];
+                let (left, top) = (x * sprite_width, y * sprite_height);
+                let right = (left + sprite_width).min(texture.width);
+                let bottom = (top + sprite_height).min(texture.height);
 
                 atlas_coords.push(Texture2DCoordinates {
-                    size, offset
+                    size: [
+                        (right - left) as f32 / texture.width as f32,
+                        (bottom - top) as f32 / texture.height as f32,
+                    ],
+                    offset: [
+                        left as f32 / texture.width as f32,
+                        top as f32 / texture.height as f32,
+                    ],
                 });
 
             }
@@ -74,12 +99,34 @@ impl SpriteSheetCoordinates {
     pub fn len(&self) -> usize {
         self.coords.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.coords.is_empty()
+    }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextureRegionError {
+    OutOfBounds { x: u32, y: u32, width: u32, height: u32 },
+    DataLength { expected: usize, actual: usize },
+}
+
+impl std::fmt::Display for TextureRegionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutOfBounds { x, y, width, height } => write!(f, "Region {width}x{height} at ({x}, {y}) is outside of the texture"),
+            Self::DataLength { expected, actual } => write!(f, "Region needs {expected} bytes of RGBA data, got {actual}"),
+        }
+    }
+}
+
+impl std::error::Error for TextureRegionError {}
+
+// Cloning shares the same GPU texture
+#[derive(Clone)]
 pub struct Texture2D {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
-    pub sampler: wgpu::Sampler,
     pub width: u32,
     pub height: u32,
 
@@ -136,21 +183,87 @@ impl Texture2D {
         );
 
 
+        Self::from_texture(context, label, texture, texture_width, texture_height)
+    }
+
+    // The color components are clamped to [0, 1] and stored as 8 bit sRGB values, like image files
+    pub fn solid_color(context: &GraphicsContext, width: u32, height: u32, color: glam::Vec4) -> Self {
+        let texel = (color.clamp(glam::Vec4::ZERO, glam::Vec4::ONE) * 255.0).round().to_array().map(|component| component as u8);
+        let data = texel.repeat(width as usize * height as usize);
+
+        Self::from_memory(context, "Solid color texture", &data, width, height)
+    }
+
+    // Uploads the compressed blocks of every level, the device must support the image format
+    pub fn from_compressed(context: &GraphicsContext, label: &str, image: &CompressedImage) -> Self {
+        let size = wgpu::Extent3d {
+            width: image.width,
+            height: image.height,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: image.levels.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: image.format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let (block_width, block_height) = image.format.block_dimensions();
+        let block_size = image.format.block_copy_size(None).unwrap_or(4);
+
+        for (level, data) in image.levels.iter().enumerate() {
+            let level_size = size.mip_level_size(level as u32, wgpu::TextureDimension::D2);
+
+            context.queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                data,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(level_size.width.div_ceil(block_width) * block_size),
+                    rows_per_image: Some(level_size.height.div_ceil(block_height)),
+                },
+                level_size.physical_size(image.format)
+            );
+        }
+
+        Self::from_texture(context, label, texture, image.width, image.height)
+    }
+
+    pub fn new_render_target(context: &GraphicsContext, label: &str, width: u32, height: u32) -> Self {
+        let texture = context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: context.config.format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        Self::from_texture(context, label, texture, width, height)
+    }
+
+    fn from_texture(context: &GraphicsContext, label: &str, texture: wgpu::Texture, texture_width: u32, texture_height: u32) -> Self {
         let view = texture.create_view(&wgpu::TextureViewDescriptor {
             label: Some(&(label.to_owned() + " texture view")),
             dimension: Some(wgpu::TextureViewDimension::D2),
             ..Default::default()
         });
-        let sampler = context.device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
-
 
         let bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Quads bind group"),
@@ -160,24 +273,66 @@ impl Texture2D {
                     binding: 0,
                     resource: wgpu::BindingResource::TextureView(&view)
                 },
-
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&sampler)
-                }
             ],
         });
 
         Self {
             texture,
             view,
-            sampler,
             width: texture_width,
             height: texture_height,
             bind_group
         }
     }
 
+    // Uploads RGBA pixels into a sub-rectangle of the texture, rows are tightly packed
+    pub fn update_region(&self, context: &GraphicsContext, x: u32, y: u32, width: u32, height: u32, rgba: &[u8]) -> Result<(), TextureRegionError> {
+        let in_bounds = x.checked_add(width).is_some_and(|right| right <= self.width)
+            && y.checked_add(height).is_some_and(|bottom| bottom <= self.height);
+
+        if !in_bounds {
+            return Err(TextureRegionError::OutOfBounds { x, y, width, height });
+        }
+
+        let expected = width as usize * height as usize * 4;
+
+        if rgba.len() != expected {
+            return Err(TextureRegionError::DataLength { expected, actual: rgba.len() });
+        }
+
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        context.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            rgba,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            }
+        );
+
+        Ok(())
+    }
+
+    pub fn size_in_bytes(&self) -> u64 {
+        (0..self.texture.mip_level_count())
+            .map(|level| compressed::level_size(self.texture.format(), self.width, self.height, level) as u64)
+            .sum()
+    }
+
     pub fn create_bind_group_layout(context: &GraphicsContext) -> wgpu::BindGroupLayout {
         context.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[
@@ -191,14 +346,6 @@ impl Texture2D {
                     },
                     count: None,
                 },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    // This should match the filterable field of the
-                    // corresponding Texture entry above. 
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
             ],
             label: Some("texture_bind_group_layout"),
         })
@@ -1,11 +1,20 @@
 pub mod graphics_export {
     pub use wgpu::Color;
+    pub use wgpu::LoadOp;
     pub use wgpu::SurfaceError;
+
+    pub use crate::graphics::color::{ClearColor, ToVec4, ToWgpuColor};
 }
 
 pub mod application_export {
     pub use winit::keyboard::KeyCode;
+    pub use winit::event::MouseButton;
+    pub use winit::event::TouchPhase;
+    pub use winit::event::WindowEvent;
 }
 
 pub use glam;
-pub use image;
\ No newline at end of file
+pub use image;
+
+#[cfg(feature = "egui")]
+pub use egui;
\ No newline at end of file
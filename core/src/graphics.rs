@@ -1,19 +1,83 @@
 pub mod renderer2d;
 pub mod camera;
+pub mod color;
 pub mod shapes;
+pub mod post_process;
+pub mod render_graph;
+pub mod tilemap;
+pub mod parallax;
+pub mod ui;
+pub mod text;
+pub mod viewport;
+#[cfg(feature = "egui")]
+pub(crate) mod egui_painter;
+
+use std::{cell::RefCell, path::{Path, PathBuf}};
 
 use wgpu::SurfaceTarget;
 
+#[derive(Debug, Clone)]
+pub struct GraphicsContextSettings {
+    pub transparent: bool,
+    // Frames queued ahead of the display, 1 gives the lowest input latency at the cost of
+    // throughput as the CPU waits on the GPU every frame, 2 and 3 let them run in parallel
+    pub max_frame_latency: u32,
+    // Width over height ratio kept by the renderer viewport, the rest of the surface is left black
+    pub maintain_aspect: Option<f32>,
+    // Replaces the first sRGB format supported by the surface, ignored with a warning when the
+    // surface does not support it
+    pub surface_format: Option<wgpu::TextureFormat>,
+}
+
+impl Default for GraphicsContextSettings {
+    fn default() -> Self {
+        Self {
+            transparent: false,
+            max_frame_latency: 2,
+            maintain_aspect: None,
+            surface_format: None,
+        }
+    }
+}
+
 pub struct GraphicsContext<'a> {
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub surface: wgpu::Surface<'a>,
     pub config: wgpu::SurfaceConfiguration,
+
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
+    adapter_info: wgpu::AdapterInfo,
+    settings: GraphicsContextSettings,
+
+    scale_factor: f64,
+
+    screenshot_request: RefCell<Option<PathBuf>>,
+
+    #[cfg(feature = "egui")]
+    egui_painter: RefCell<Option<egui_painter::EguiPainter>>,
+    #[cfg(feature = "egui")]
+    egui_frame: RefCell<Option<egui_painter::EguiFrame>>,
 }
 
 
 impl<'a> GraphicsContext<'a> {
+    const OPTIONAL_FEATURES: wgpu::Features = wgpu::Features::ADDRESS_MODE_CLAMP_TO_BORDER
+        .union(wgpu::Features::TEXTURE_COMPRESSION_BC)
+        .union(wgpu::Features::TEXTURE_COMPRESSION_ETC2)
+        .union(wgpu::Features::TEXTURE_COMPRESSION_ASTC);
+
     pub async fn new(surface_target: impl Into<SurfaceTarget<'a>>, surface_width: u32, surface_height: u32) -> Self {
+        Self::with_settings(surface_target, surface_width, surface_height, &Default::default()).await
+    }
+
+    pub async fn with_settings(
+        surface_target: impl Into<SurfaceTarget<'a>>,
+        surface_width: u32,
+        surface_height: u32,
+        settings: &GraphicsContextSettings
+    ) -> Self {
 
 
         log::info!("Creating instance");
@@ -42,7 +106,9 @@ impl<'a> GraphicsContext<'a> {
 
         let (device, queue) = adapter.request_device(&wgpu::DeviceDescriptor {
             label: Some("Graphics context device"),
-            required_features: wgpu::Features::TEXTURE_BINDING_ARRAY,
+            // Border clamping and compressed formats are optional, the renderer falls back to edge
+            // clamping and the assets manager to uncompressed textures without them
+            required_features: wgpu::Features::TEXTURE_BINDING_ARRAY | (adapter.features() & Self::OPTIONAL_FEATURES),
             required_limits: wgpu::Limits::defaults(),
             memory_hints: Default::default(),
             trace: wgpu::Trace::Off,
@@ -51,44 +117,327 @@ impl<'a> GraphicsContext<'a> {
         .unwrap();
 
 
-        let surface_caps = surface.get_capabilities(&adapter);
+        log::info!("Configuring the surface");
+
+        let config = Self::configure_surface(&adapter, &device, &surface, surface_width, surface_height, settings);
+
+
+        Self {
+            config,
+            device,
+            queue,
+            surface,
+
+            instance,
+            adapter_info: adapter.get_info(),
+            adapter,
+            settings: settings.clone(),
+
+            scale_factor: 1.0,
+
+            screenshot_request: RefCell::new(None),
+
+            #[cfg(feature = "egui")]
+            egui_painter: RefCell::new(None),
+            #[cfg(feature = "egui")]
+            egui_frame: RefCell::new(None),
+        }
+
+    }
+
 
-        let surface_format = surface_caps.formats.iter()
+    // Creates a context for another window that shares this context device and queue, so textures
+    // and pipelines created with one can be used with the other
+    pub fn share_device<'b>(&self, surface_target: impl Into<SurfaceTarget<'b>>, surface_width: u32, surface_height: u32) -> GraphicsContext<'b> {
+        log::info!("Creating shared surface");
+        let surface = self.instance.create_surface(surface_target).unwrap();
+
+        let config = Self::configure_surface(&self.adapter, &self.device, &surface, surface_width, surface_height, &self.settings);
+
+        GraphicsContext {
+            config,
+            device: self.device.clone(),
+            queue: self.queue.clone(),
+            surface,
+
+            instance: self.instance.clone(),
+            adapter: self.adapter.clone(),
+            adapter_info: self.adapter_info.clone(),
+            settings: self.settings.clone(),
+
+            scale_factor: 1.0,
+
+            screenshot_request: RefCell::new(None),
+
+            #[cfg(feature = "egui")]
+            egui_painter: RefCell::new(None),
+            #[cfg(feature = "egui")]
+            egui_frame: RefCell::new(None),
+        }
+    }
+
+    fn configure_surface(
+        adapter: &wgpu::Adapter,
+        device: &wgpu::Device,
+        surface: &wgpu::Surface,
+        surface_width: u32,
+        surface_height: u32,
+        settings: &GraphicsContextSettings
+    ) -> wgpu::SurfaceConfiguration {
+        let surface_caps = surface.get_capabilities(adapter);
+
+        let default_format = surface_caps.formats.iter()
         .find(|format| format.is_srgb())
         .copied()
         .unwrap_or(surface_caps.formats[0]);
 
+        let surface_format = match settings.surface_format {
+            Some(format) if surface_caps.formats.contains(&format) => format,
+            Some(format) => {
+                log::warn!("Surface format {format:?} is not supported, using {default_format:?}. Supported formats: {:?}", surface_caps.formats);
+                default_format
+            }
+            None => default_format,
+        };
+
+        let alpha_mode = if settings.transparent {
+            Self::select_transparent_alpha_mode(&surface_caps.alpha_modes)
+        }
+        else {
+            surface_caps.alpha_modes[0]
+        };
+
+        // Copying from the surface texture is needed for screenshots
+        let usage = wgpu::TextureUsages::RENDER_ATTACHMENT | (surface_caps.usages & wgpu::TextureUsages::COPY_SRC);
+
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage,
             format: surface_format,
             width: surface_width,
             height: surface_height,
             present_mode: surface_caps.present_modes[0],
-            alpha_mode: surface_caps.alpha_modes[0],
+            alpha_mode,
             view_formats: vec![],
-            desired_maximum_frame_latency: 2,
+            desired_maximum_frame_latency: Self::clamp_frame_latency(settings.max_frame_latency),
         };
 
-        log::info!("Configuring the surface");
+        surface.configure(device, &config);
 
-        surface.configure(&device, &config);
+        config
+    }
 
+    #[cfg(feature = "egui")]
+    pub(crate) fn set_egui_frame(&self, mut frame: egui_painter::EguiFrame) {
+        // Texture updates of a frame that was never presented must not be lost
+        if let Some(pending) = self.egui_frame.take() {
+            let mut textures_delta = pending.textures_delta;
+            textures_delta.append(frame.textures_delta);
+            frame.textures_delta = textures_delta;
+        }
 
-        Self {
-            config,
-            device,
-            queue,
-            surface,
+        self.egui_frame.replace(Some(frame));
+    }
+
+    // Draws what is rendered on top of the renderer output (debug UI) before the surface is presented
+    pub(crate) fn render_overlays(&self, _surface_texture: &wgpu::Texture) {
+        #[cfg(feature = "egui")]
+        if let Some(frame) = self.egui_frame.take() {
+            self.egui_painter.borrow_mut()
+                .get_or_insert_with(|| egui_painter::EguiPainter::new(self))
+                .paint(self, _surface_texture, frame);
+        }
+    }
+
+    // The screenshot is taken from the next frame presented by the renderer
+    pub fn request_screenshot(&self, path: impl Into<PathBuf>) {
+        self.screenshot_request.replace(Some(path.into()));
+    }
+
+    pub(crate) fn capture_pending_screenshot(&self, surface_texture: &wgpu::Texture) {
+        let Some(path) = self.screenshot_request.take() else {
+            return;
+        };
+
+        if !surface_texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            log::warn!("Screenshots are not supported by this surface");
+            return;
+        }
+
+        if let Err(e) = self.save_texture(surface_texture, &path) {
+            log::error!("Failed to save screenshot {}: {e}", path.display());
+        }
+    }
+
+    fn save_texture(&self, texture: &wgpu::Texture, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let (width, height) = (texture.width(), texture.height());
+
+        let unpadded_bytes_per_row = 4 * width;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Screenshot buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Screenshot commands encoder"),
+        });
+
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size()
+        );
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| ());
+        self.device.poll(wgpu::PollType::Wait)?;
+
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+        for row in slice.get_mapped_range().chunks(padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+        buffer.unmap();
+
+        if matches!(texture.format(), wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb) {
+            pixels.chunks_mut(4).for_each(|pixel| pixel.swap(0, 2));
+        }
+
+        image::save_buffer(path, &pixels, width, height, image::ColorType::Rgba8)?;
+
+        log::info!("Screenshot saved to {}", path.display());
+
+        Ok(())
+    }
+
+    // wgpu further limits the latency to the number of images the backend swapchain supports
+    fn clamp_frame_latency(max_frame_latency: u32) -> u32 {
+        const SUPPORTED_LATENCY: std::ops::RangeInclusive<u32> = 1..=3;
+
+        let clamped = max_frame_latency.clamp(*SUPPORTED_LATENCY.start(), *SUPPORTED_LATENCY.end());
+
+        if clamped != max_frame_latency {
+            log::warn!("Maximum frame latency {max_frame_latency} is not supported, using {clamped}");
         }
 
+        clamped
     }
 
+    fn select_transparent_alpha_mode(alpha_modes: &[wgpu::CompositeAlphaMode]) -> wgpu::CompositeAlphaMode {
+        let transparent_modes = [
+            wgpu::CompositeAlphaMode::PreMultiplied,
+            wgpu::CompositeAlphaMode::PostMultiplied,
+            wgpu::CompositeAlphaMode::Inherit,
+        ];
 
-    pub(crate) fn resize_surface(&mut self, width: u32, height: u32) {
+        transparent_modes.into_iter()
+        .find(|mode| alpha_modes.contains(mode))
+        .unwrap_or_else(|| {
+            log::warn!("Surface transparency is not supported on this platform, supported alpha modes {alpha_modes:?}");
+            alpha_modes[0]
+        })
+    }
+
+    // Format of the surface textures, render pipelines drawing to the surface must target it
+    pub fn surface_format(&self) -> wgpu::TextureFormat {
+        self.config.format
+    }
+
+    // Features enabled on the device, the optional ones (border clamping, compressed formats) are
+    // only there when the adapter supports them
+    pub fn features(&self) -> wgpu::Features {
+        self.device.features()
+    }
+
+    pub fn limits(&self) -> wgpu::Limits {
+        self.device.limits()
+    }
+
+    // Name, backend and driver of the GPU the device was created on
+    pub fn adapter_info(&self) -> &wgpu::AdapterInfo {
+        &self.adapter_info
+    }
+
+    // Ratio between physical and logical pixels of the window the surface belongs to
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+    }
+
+    // The surface size in physical pixels, the size of the rendered frame
+    pub fn physical_size(&self) -> (u32, u32) {
+        (self.config.width, self.config.height)
+    }
+
+    // The surface size in logical pixels, use it for the camera viewport to keep the same
+    // apparent size of the drawn quads whatever the display density is
+    pub fn logical_size(&self) -> (f32, f32) {
+        (
+            (self.config.width as f64 / self.scale_factor) as f32,
+            (self.config.height as f64 / self.scale_factor) as f32,
+        )
+    }
+
+    pub fn maintain_aspect(&self) -> Option<f32> {
+        self.settings.maintain_aspect
+    }
+
+    pub fn set_maintain_aspect(&mut self, aspect: Option<f32>) {
+        self.settings.maintain_aspect = aspect;
+    }
+
+    // Position and size in physical pixels of the area the renderer draws to, the whole surface
+    // unless an aspect ratio is maintained
+    pub fn viewport_rect(&self) -> (glam::Vec2, glam::Vec2) {
+        letterbox_rect(self.config.width, self.config.height, self.settings.maintain_aspect)
+    }
+
+    // Converts a cursor position on the surface to the viewport, positions in the black bars
+    // end up outside of the viewport size
+    pub fn surface_to_viewport(&self, position: glam::Vec2) -> glam::Vec2 {
+        position - self.viewport_rect().0
+    }
+
+    // Reconfigures the surface, needed when driving the context from a custom window loop.
+    // Zero sized dimensions (minimized window) are ignored
+    pub fn resize(&mut self, width: u32, height: u32) {
         if width > 0 && height > 0 {
             self.config.width = width;
             self.config.height = height;
             self.surface.configure(&self.device, &self.config);
         }
     }
-}
\ No newline at end of file
+}
+
+// The largest rect of the aspect ratio centered in the surface
+pub(crate) fn letterbox_rect(width: u32, height: u32, aspect: Option<f32>) -> (glam::Vec2, glam::Vec2) {
+    let surface_size = glam::vec2(width as f32, height as f32);
+
+    let Some(aspect) = aspect.filter(|aspect| aspect.is_finite() && *aspect > 0.0) else {
+        return (glam::Vec2::ZERO, surface_size);
+    };
+
+    let size = if surface_size.x / surface_size.y > aspect {
+        glam::vec2((surface_size.y * aspect).round(), surface_size.y)
+    }
+    else {
+        glam::vec2(surface_size.x, (surface_size.x / aspect).round())
+    }
+    .max(glam::Vec2::ONE);
+
+    (((surface_size - size) * 0.5).floor(), size)
+}
@@ -1,24 +1,192 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::math::{damp, rng::Rng};
+
+// Each camera shakes with its own rng stream so two cameras never move in sync
+static NEXT_SHAKE_STREAM: AtomicU64 = AtomicU64::new(0);
+
+const SHAKE_SEED: u64 = 0x5eed;
+
+// How a camera of a fixed design size adapts to windows of another aspect ratio
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FitMode {
+    // The design area fills the window and is distorted when the aspects differ
+    #[default]
+    Stretch,
+    // The design area is kept undistorted, meant to be used with `GraphicsContext::set_maintain_aspect`
+    // set to the design aspect which adds the black bars
+    Letterbox,
+    // The design area is centered and the view grows along the window's longer axis, wider windows
+    // show more of the world on the sides and taller ones above and below
+    Expand,
+}
+
+#[derive(Debug, Clone)]
 pub struct Camera2D {
     view_proj: glam::Mat4,
+    viewport_size: glam::Vec2,
+    design_size: glam::Vec2,
+    fit_mode: FitMode,
+    // World point at the center of the view
+    center: glam::Vec2,
+
+    trauma: f32,
+    trauma_decay: f32,
+    max_shake_offset: glam::Vec2,
+    shake_offset: glam::Vec2,
+    shake_rng: Rng,
+}
+
+// Unit viewport, the camera is meant to be sized with `Camera2D::new` before drawing
+impl Default for Camera2D {
+    fn default() -> Self {
+        Self::new(1.0, 1.0)
+    }
 }
 
 impl Camera2D {
+    // Draw coordinates are in viewport units, a viewport of `GraphicsContext::logical_size` maps
+    // them to logical pixels while `GraphicsContext::physical_size` maps them to device pixels
     pub fn new(viewport_width: f32, viewport_height: f32) -> Self {
+        Self::with_fit_mode(viewport_width, viewport_height, FitMode::Stretch)
+    }
+
+    // The camera shows the design area until `resize` is called with the window size
+    pub fn with_fit_mode(design_width: f32, design_height: f32, fit_mode: FitMode) -> Self {
+        let design_size = glam::vec2(design_width, design_height);
 
         Self {
-           view_proj: glam::Mat4::orthographic_lh(0.0,viewport_width, viewport_height, 0.0, 0.0, 1.0)
+           view_proj: glam::Mat4::orthographic_lh(0.0, design_width, design_height, 0.0, 0.0, 1.0),
+           viewport_size: design_size,
+           design_size,
+           fit_mode,
+           center: design_size * 0.5,
+
+           trauma: 0.0,
+           trauma_decay: 1.0,
+           max_shake_offset: design_size * 0.02,
+           shake_offset: glam::Vec2::ZERO,
+           shake_rng: Rng::with_stream(SHAKE_SEED, NEXT_SHAKE_STREAM.fetch_add(1, Ordering::Relaxed)),
+        }
+    }
+
+    // Makes the shake reproducible, cameras with the same seed shake the same way
+    pub fn with_shake_seed(mut self, seed: u64) -> Self {
+        self.shake_rng = Rng::new(seed);
+        self
+    }
+
+    // Starts at the center of the design area, the view then spans from the origin to the design size
+    pub fn center(&self) -> glam::Vec2 {
+        self.center
+    }
+
+    pub fn set_center(&mut self, center: glam::Vec2) {
+        self.center = center;
+    }
+
+    // Moves the view center towards `target` with `math::damp`, called once per frame.
+    // `lambda` is how fast the distance shrinks per second
+    pub fn follow(&mut self, target: glam::Vec2, lambda: f32, dt: f32) {
+        self.center = damp(self.center, target, lambda, dt);
+    }
+
+    // Trauma is kept in [0, 1] and the shake grows with its square, small hits barely move the camera
+    pub fn add_shake(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).clamp(0.0, 1.0);
+    }
+
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    // `max_offset` is the displacement at full trauma in viewport units, defaults to 2% of the viewport.
+    // `decay` is the trauma lost per second
+    pub fn set_shake(&mut self, max_offset: glam::Vec2, decay: f32) {
+        self.max_shake_offset = max_offset;
+        self.trauma_decay = decay.max(0.0);
+    }
+
+    // Decays the trauma and picks a new shake offset, called once per frame
+    pub fn update(&mut self, dt: f32) {
+        self.trauma = (self.trauma - self.trauma_decay * dt).max(0.0);
+
+        let shake = self.trauma * self.trauma;
+        let direction = self.shake_rng.next_vec2_in_rect(-glam::Vec2::ONE, glam::Vec2::ONE);
+
+        self.shake_offset = self.max_shake_offset * shake * direction;
+    }
+
+    // World space (min, max) corners of the area seen by the camera
+    pub fn visible_rect(&self) -> (glam::Vec2, glam::Vec2) {
+        let inverse = self.to_matrix().inverse();
+
+        let a = inverse.project_point3(glam::vec3(-1.0, -1.0, 0.0)).truncate();
+        let b = inverse.project_point3(glam::vec3(1.0, 1.0, 0.0)).truncate();
+
+        (a.min(b), a.max(b))
+    }
+
+    pub fn viewport_size(&self) -> glam::Vec2 {
+        self.viewport_size
+    }
+
+    pub fn design_size(&self) -> glam::Vec2 {
+        self.design_size
+    }
+
+    pub fn fit_mode(&self) -> FitMode {
+        self.fit_mode
+    }
+
+    // Adapts the projection to the aspect of the area drawn to, usually `GraphicsContext::viewport_rect`.
+    // Only `FitMode::Expand` changes the projection, the design area stays at the same world position
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
         }
+
+        let design = self.design_size;
+
+        let size = match self.fit_mode {
+            FitMode::Stretch | FitMode::Letterbox => design,
+            FitMode::Expand => {
+                let aspect = width as f32 / height as f32;
+
+                if aspect > design.x / design.y {
+                    glam::vec2(design.y * aspect, design.y)
+                }
+                else {
+                    glam::vec2(design.x, design.x / aspect)
+                }
+            }
+        };
+
+        let margin = (size - design) * 0.5;
+
+        self.viewport_size = size;
+        self.view_proj = glam::Mat4::orthographic_lh(-margin.x, design.x + margin.x, design.y + margin.y, -margin.y, 0.0, 1.0);
+    }
+
+    // Converts a position in pixels of the area the camera is drawn to, origin at its top left
+    // corner, to world space. The shake offset is applied like in `to_matrix`
+    pub fn screen_to_world(&self, position: glam::Vec2, screen_size: glam::Vec2) -> glam::Vec2 {
+        let ndc = position / screen_size.max(glam::Vec2::ONE) * glam::vec2(2.0, -2.0) + glam::vec2(-1.0, 1.0);
+
+        self.to_matrix().inverse().project_point3(ndc.extend(0.0)).truncate()
     }
 
 
     pub fn to_matrix(&self) -> glam::Mat4 {
-        self.view_proj
-    } 
+        let offset = self.shake_offset + self.design_size * 0.5 - self.center;
+
+        self.view_proj * glam::Mat4::from_translation(offset.extend(0.0))
+    }
 }
 
 
 #[repr(C)]
-#[derive(bytemuck::Zeroable, bytemuck::Pod, Copy, Clone)]
+#[derive(bytemuck::Zeroable, bytemuck::Pod, Copy, Clone, Debug, PartialEq)]
 pub(crate) struct CameraUniform {
     view_proj: glam::Mat4
 }
@@ -29,4 +197,102 @@ impl CameraUniform {
             view_proj: matrix
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cloned_camera_has_equal_matrices() {
+        let mut camera = Camera2D::with_fit_mode(320.0, 180.0, FitMode::Expand);
+        camera.resize(1920, 1200);
+        camera.add_shake(0.8);
+        camera.update(0.016);
+
+        let mut cloned = camera.clone();
+
+        assert_eq!(cloned.to_matrix(), camera.to_matrix());
+        assert_eq!(cloned.viewport_size(), camera.viewport_size());
+        assert_eq!(cloned.visible_rect(), camera.visible_rect());
+
+        // The shake state is cloned too, both cameras keep shaking the same way
+        camera.update(0.016);
+        cloned.update(0.016);
+
+        assert_eq!(cloned.to_matrix(), camera.to_matrix());
+    }
+
+    #[test]
+    fn default_camera_has_unit_viewport() {
+        let camera = Camera2D::default();
+
+        assert_eq!(camera.viewport_size(), glam::Vec2::ONE);
+        assert_eq!(camera.to_matrix(), Camera2D::new(1.0, 1.0).to_matrix());
+    }
+
+    #[test]
+    fn follow_centers_the_view_on_the_target() {
+        let mut camera = Camera2D::new(800.0, 600.0);
+        assert_eq!(camera.visible_rect(), (glam::Vec2::ZERO, glam::vec2(800.0, 600.0)));
+
+        let target = glam::vec2(1000.0, -200.0);
+
+        camera.follow(target, 5.0, 0.1);
+        let halfway = camera.center();
+        assert!(halfway.distance(target) < glam::vec2(400.0, 300.0).distance(target));
+
+        // Same step as `math::damp`, the view converges on the target
+        assert_eq!(halfway, damp(glam::vec2(400.0, 300.0), target, 5.0, 0.1));
+
+        camera.follow(target, 5.0, 10.0);
+        let (min, max) = camera.visible_rect();
+        assert!(((min + max) * 0.5).abs_diff_eq(target, 1e-2));
+        assert!((max - min).abs_diff_eq(glam::vec2(800.0, 600.0), 1e-2));
+    }
+
+    fn shaken_matrix(camera: Camera2D) -> glam::Mat4 {
+        let mut camera = camera;
+        camera.add_shake(1.0);
+        camera.update(0.0);
+
+        camera.to_matrix()
+    }
+
+    #[test]
+    fn cameras_shake_independently_unless_seeded() {
+        assert_ne!(shaken_matrix(Camera2D::new(800.0, 600.0)), shaken_matrix(Camera2D::new(800.0, 600.0)));
+
+        assert_eq!(
+            shaken_matrix(Camera2D::new(800.0, 600.0).with_shake_seed(7)),
+            shaken_matrix(Camera2D::new(800.0, 600.0).with_shake_seed(7))
+        );
+    }
+
+    #[test]
+    fn trauma_is_clamped() {
+        let mut camera = Camera2D::new(800.0, 600.0);
+
+        camera.add_shake(0.7);
+        camera.add_shake(0.7);
+        assert_eq!(camera.trauma(), 1.0);
+
+        camera.add_shake(-3.0);
+        assert_eq!(camera.trauma(), 0.0);
+    }
+
+    #[test]
+    fn trauma_decays_over_time() {
+        let mut camera = Camera2D::new(800.0, 600.0);
+        camera.set_shake(glam::vec2(10.0, 10.0), 0.5);
+        camera.add_shake(1.0);
+
+        camera.update(1.0);
+        assert_eq!(camera.trauma(), 0.5);
+
+        // The trauma stops at zero and the camera is back at rest
+        camera.update(2.0);
+        assert_eq!(camera.trauma(), 0.0);
+        assert_eq!(camera.to_matrix(), Camera2D::new(800.0, 600.0).to_matrix());
+    }
+}
@@ -0,0 +1,75 @@
+// `glam::Vec4` and `wgpu::Color` are both foreign types so `From` can not be implemented between
+// them, these traits convert in both directions, e.g. `LoadOp::Clear(quad.color.to_wgpu_color())`
+
+pub trait ToWgpuColor {
+    fn to_wgpu_color(self) -> wgpu::Color;
+}
+
+impl ToWgpuColor for glam::Vec4 {
+    fn to_wgpu_color(self) -> wgpu::Color {
+        wgpu::Color {
+            r: self.x as f64,
+            g: self.y as f64,
+            b: self.z as f64,
+            a: self.w as f64,
+        }
+    }
+}
+
+pub trait ToVec4 {
+    fn to_vec4(self) -> glam::Vec4;
+}
+
+impl ToVec4 for wgpu::Color {
+    fn to_vec4(self) -> glam::Vec4 {
+        glam::vec4(self.r as f32, self.g as f32, self.b as f32, self.a as f32)
+    }
+}
+
+// Clear color taken by `Renderer2D::begin`, built from a `glam::Vec4`, a `wgpu::Color` or `None`
+// which keeps the current clear color, e.g. `renderer.begin(quad.color.into(), &camera)`.
+// Named so it does not shadow the `wgpu::Color` re-export
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ClearColor(pub Option<wgpu::Color>);
+
+impl From<glam::Vec4> for ClearColor {
+    fn from(color: glam::Vec4) -> Self {
+        Self(Some(color.to_wgpu_color()))
+    }
+}
+
+impl From<wgpu::Color> for ClearColor {
+    fn from(color: wgpu::Color) -> Self {
+        Self(Some(color))
+    }
+}
+
+impl From<Option<wgpu::Color>> for ClearColor {
+    fn from(color: Option<wgpu::Color>) -> Self {
+        Self(color)
+    }
+}
+
+impl From<ClearColor> for Option<wgpu::Color> {
+    fn from(color: ClearColor) -> Self {
+        color.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clear_color(color: impl Into<ClearColor>) -> Option<wgpu::Color> {
+        color.into().into()
+    }
+
+    #[test]
+    fn clear_color_converts_from_vec4_and_wgpu_color() {
+        let color = glam::vec4(0.25, 0.5, 0.75, 1.0);
+
+        assert_eq!(clear_color(color), Some(wgpu::Color { r: 0.25, g: 0.5, b: 0.75, a: 1.0 }));
+        assert_eq!(clear_color(wgpu::Color::RED), Some(wgpu::Color::RED));
+        assert_eq!(clear_color(None), None);
+    }
+}
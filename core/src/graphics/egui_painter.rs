@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+
+use wgpu::util::DeviceExt;
+
+use crate::graphics::GraphicsContext;
+
+
+pub(crate) struct EguiFrame {
+    pub primitives: Vec<egui::ClippedPrimitive>,
+    pub textures_delta: egui::TexturesDelta,
+    pub pixels_per_point: f32,
+}
+
+struct EguiTexture {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+}
+
+pub(crate) struct EguiPainter {
+    render_pipeline: wgpu::RenderPipeline,
+    locals_buffer: wgpu::Buffer,
+    locals_bind_group: wgpu::BindGroup,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    textures: HashMap<egui::TextureId, EguiTexture>,
+}
+
+impl EguiPainter {
+    pub fn new(context: &GraphicsContext) -> Self {
+        let shader = context.device.create_shader_module(wgpu::include_wgsl!("../../assets/shaders/egui.wgsl"));
+
+        let locals_bind_group_layout = context.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Egui locals bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ],
+        });
+
+        let texture_bind_group_layout = context.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Egui texture bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let render_pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Egui pipeline layout"),
+            bind_group_layouts: &[
+                &locals_bind_group_layout,
+                &texture_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let fragment_entry_point = if context.config.format.is_srgb() {
+            "fs_main_linear_framebuffer"
+        }
+        else {
+            "fs_main_gamma_framebuffer"
+        };
+
+        let render_pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Egui pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<egui::epaint::Vertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Uint32],
+                }],
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some(fragment_entry_point),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: context.config.format,
+                    // egui outputs premultiplied colors
+                    blend: Some(wgpu::BlendState {
+                        color: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::One,
+                            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                        alpha: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::OneMinusDstAlpha,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            })
+        });
+
+        let locals_buffer = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Egui locals buffer"),
+            size: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let locals_bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Egui locals bind group"),
+            layout: &locals_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: locals_buffer.as_entire_binding(),
+                }
+            ],
+        });
+
+        Self {
+            render_pipeline,
+            locals_buffer,
+            locals_bind_group,
+            texture_bind_group_layout,
+            textures: HashMap::new(),
+        }
+    }
+
+    pub fn paint(&mut self, context: &GraphicsContext, surface_texture: &wgpu::Texture, frame: EguiFrame) {
+        for (id, delta) in &frame.textures_delta.set {
+            self.update_texture(context, *id, delta);
+        }
+
+        let (width, height) = (surface_texture.width(), surface_texture.height());
+        let screen_size = [width as f32 / frame.pixels_per_point, height as f32 / frame.pixels_per_point, 0.0, 0.0];
+        context.queue.write_buffer(&self.locals_buffer, 0, bytemuck::cast_slice(&screen_size));
+
+        let meshes = frame.primitives.iter()
+            .filter_map(|clipped| match &clipped.primitive {
+                egui::epaint::Primitive::Mesh(mesh) if !mesh.indices.is_empty() => Some((clipped.clip_rect, mesh)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if !meshes.is_empty() {
+            let vertices = meshes.iter().flat_map(|(_, mesh)| mesh.vertices.iter().copied()).collect::<Vec<_>>();
+            let indices = meshes.iter().flat_map(|(_, mesh)| mesh.indices.iter().copied()).collect::<Vec<_>>();
+
+            let vertex_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Egui vertex buffer"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+
+            let index_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Egui index buffer"),
+                contents: bytemuck::cast_slice(&indices),
+                usage: wgpu::BufferUsages::INDEX,
+            });
+
+            let view = surface_texture.create_view(&Default::default());
+
+            let mut encoder = context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Egui commands encoder"),
+            });
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Egui render pass"),
+                color_attachments: &[
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: &view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                        depth_slice: None,
+                    })
+                ],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            render_pass.set_pipeline(&self.render_pipeline);
+            render_pass.set_bind_group(0, &self.locals_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+            let mut base_vertex = 0;
+            let mut first_index = 0;
+
+            for (clip_rect, mesh) in meshes {
+                let index_count = mesh.indices.len() as u32;
+                let vertex_count = mesh.vertices.len() as i32;
+
+                let min_x = (clip_rect.min.x * frame.pixels_per_point).round().clamp(0.0, width as f32) as u32;
+                let min_y = (clip_rect.min.y * frame.pixels_per_point).round().clamp(0.0, height as f32) as u32;
+                let max_x = (clip_rect.max.x * frame.pixels_per_point).round().clamp(min_x as f32, width as f32) as u32;
+                let max_y = (clip_rect.max.y * frame.pixels_per_point).round().clamp(min_y as f32, height as f32) as u32;
+
+                if let (Some(texture), true) = (self.textures.get(&mesh.texture_id), max_x > min_x && max_y > min_y) {
+                    render_pass.set_scissor_rect(min_x, min_y, max_x - min_x, max_y - min_y);
+                    render_pass.set_bind_group(1, &texture.bind_group, &[]);
+                    render_pass.draw_indexed(first_index..first_index + index_count, base_vertex, 0..1);
+                }
+
+                first_index += index_count;
+                base_vertex += vertex_count;
+            }
+
+            drop(render_pass);
+
+            context.queue.submit(std::iter::once(encoder.finish()));
+        }
+
+        for id in &frame.textures_delta.free {
+            self.textures.remove(id);
+        }
+    }
+
+    fn update_texture(&mut self, context: &GraphicsContext, id: egui::TextureId, delta: &egui::epaint::ImageDelta) {
+        let egui::ImageData::Color(image) = &delta.image;
+        let [width, height] = image.size;
+
+        let size = wgpu::Extent3d {
+            width: width as u32,
+            height: height as u32,
+            depth_or_array_layers: 1,
+        };
+
+        let origin = match delta.pos {
+            Some([x, y]) => wgpu::Origin3d { x: x as u32, y: y as u32, z: 0 },
+            None => {
+                let texture = self.create_texture(context, size, &delta.options);
+                self.textures.insert(id, texture);
+
+                wgpu::Origin3d::ZERO
+            }
+        };
+
+        let Some(texture) = self.textures.get(&id) else {
+            log::warn!("Egui tried to update the unknown texture {id:?}");
+            return;
+        };
+
+        context.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture.texture,
+                mip_level: 0,
+                origin,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(&image.pixels),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width as u32),
+                rows_per_image: Some(height as u32),
+            },
+            size
+        );
+    }
+
+    fn create_texture(&self, context: &GraphicsContext, size: wgpu::Extent3d, options: &egui::TextureOptions) -> EguiTexture {
+        let texture = context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Egui texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let filter_mode = |filter: egui::TextureFilter| match filter {
+            egui::TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+            egui::TextureFilter::Linear => wgpu::FilterMode::Linear,
+        };
+
+        let address_mode = match options.wrap_mode {
+            egui::TextureWrapMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+            egui::TextureWrapMode::Repeat => wgpu::AddressMode::Repeat,
+            egui::TextureWrapMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+        };
+
+        let sampler = context.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Egui sampler"),
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            mag_filter: filter_mode(options.magnification),
+            min_filter: filter_mode(options.minification),
+            ..Default::default()
+        });
+
+        let view = texture.create_view(&Default::default());
+
+        let bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Egui texture bind group"),
+            layout: &self.texture_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler)
+                }
+            ],
+        });
+
+        EguiTexture {
+            texture,
+            bind_group,
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use crate::assets::{texture::Texture2D, AssetHandle};
+
+
+// A texture repeated across the view that scrolls at a fraction of the camera speed, a scroll
+// factor of 0 keeps it fixed on screen while 1 moves it with the world
+#[derive(Debug, Clone, Copy)]
+pub struct ParallaxLayer {
+    pub texture: AssetHandle<Texture2D>,
+    pub scroll_factor: glam::Vec2,
+    pub z_index: i32,
+
+    pub tile_size: glam::Vec2,
+    pub offset: glam::Vec2,
+}
+
+impl ParallaxLayer {
+    pub fn new(texture: AssetHandle<Texture2D>, tile_size: glam::Vec2, scroll_factor: glam::Vec2) -> Self {
+        Self {
+            texture,
+            scroll_factor,
+            z_index: 0,
+            tile_size,
+            offset: glam::Vec2::ZERO,
+        }
+    }
+}
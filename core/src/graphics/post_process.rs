@@ -0,0 +1,349 @@
+use std::cell::{Cell, RefCell};
+
+use wgpu::util::DeviceExt;
+
+use crate::graphics::GraphicsContext;
+
+const POST_PROCESS_VERTEX_SHADER: &str = include_str!("../../assets/shaders/post_process.wgsl");
+
+// Linear format of the targets between two passes, the passes output linear colors and the sRGB
+// encoding is left to the surface
+const INTERMEDIATE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+// Each pass has a pipeline for the intermediate targets and one for the output, only the last pass
+// draws to the output
+struct PostProcessPass {
+    intermediate_pipeline: wgpu::RenderPipeline,
+    output_pipeline: wgpu::RenderPipeline,
+    intermediate_params_bind_group: wgpu::BindGroup,
+    output_params_bind_group: wgpu::BindGroup,
+}
+
+struct PostProcessTarget {
+    view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+
+pub struct PostProcessChain {
+    passes: Vec<PostProcessPass>,
+
+    source_bind_group_layout: wgpu::BindGroupLayout,
+    params_bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    linear_output_buffer: wgpu::Buffer,
+    srgb_output_buffer: wgpu::Buffer,
+
+    // The scene target in the surface format, drawn by the renderer pipelines, then the intermediate targets
+    targets: RefCell<Vec<PostProcessTarget>>,
+    targets_size: Cell<(u32, u32)>,
+}
+
+impl PostProcessChain {
+    pub fn new(context: &GraphicsContext) -> Self {
+        let source_bind_group_layout = context.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Post process source bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let params_bind_group_layout = context.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Post process params bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ],
+        });
+
+        let output_srgb_buffer = |label, srgb: bool| context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::cast_slice(&[if srgb { 1.0f32 } else { 0.0 }]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let linear_output_buffer = output_srgb_buffer("Post process linear output buffer", false);
+        let srgb_output_buffer = output_srgb_buffer("Post process output buffer", context.config.format.is_srgb());
+
+        let sampler = context.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post process sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            passes: Vec::new(),
+            source_bind_group_layout,
+            params_bind_group_layout,
+            sampler,
+            linear_output_buffer,
+            srgb_output_buffer,
+            targets: RefCell::new(Vec::new()),
+            targets_size: Cell::new((0, 0)),
+        }
+    }
+
+    pub fn with_passthrough(self, context: &GraphicsContext) -> Self {
+        self.with_shader(
+            context,
+            "Post process passthrough",
+            include_str!("../../assets/shaders/post_process_passthrough.wgsl"),
+            [0.0; 4]
+        )
+    }
+
+    pub fn with_gamma(self, context: &GraphicsContext, gamma: f32, exposure: f32) -> Self {
+        self.with_shader(
+            context,
+            "Post process gamma",
+            include_str!("../../assets/shaders/post_process_gamma.wgsl"),
+            [gamma, exposure, 0.0, 0.0]
+        )
+    }
+
+    // The fragment source is appended to the shared full-screen vertex shader, it has access to
+    // `source_texture`, `source_sampler`, `params`, `output_srgb` and must define `fs_main(in: VertexOutput)`.
+    // Colors are linear, the sRGB encoding is done by the output target when `output_srgb` is 1
+    pub fn with_shader(mut self, context: &GraphicsContext, label: &str, fragment_source: &str, params: [f32; 4]) -> Self {
+        let shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl((POST_PROCESS_VERTEX_SHADER.to_owned() + fragment_source).into()),
+        });
+
+        let render_pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &[
+                &self.source_bind_group_layout,
+                &self.params_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let intermediate_pipeline = Self::create_pipeline(context, label, &render_pipeline_layout, &shader, INTERMEDIATE_FORMAT);
+        let output_pipeline = Self::create_pipeline(context, label, &render_pipeline_layout, &shader, context.config.format);
+
+        let params_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post process params buffer"),
+            contents: bytemuck::cast_slice(&params),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let params_bind_group = |output_buffer: &wgpu::Buffer| context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Post process params bind group"),
+            layout: &self.params_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: output_buffer.as_entire_binding(),
+                }
+            ],
+        });
+
+        let intermediate_params_bind_group = params_bind_group(&self.linear_output_buffer);
+        let output_params_bind_group = params_bind_group(&self.srgb_output_buffer);
+
+        self.passes.push(PostProcessPass {
+            intermediate_pipeline,
+            output_pipeline,
+            intermediate_params_bind_group,
+            output_params_bind_group,
+        });
+
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    pub(crate) fn resize(&self, context: &GraphicsContext, width: u32, height: u32) {
+        self.ensure_targets(context, width, height);
+    }
+
+    pub(crate) fn scene_view(&self, context: &GraphicsContext) -> wgpu::TextureView {
+        self.ensure_targets(context, context.config.width, context.config.height);
+
+        self.targets.borrow()[0].view.clone()
+    }
+
+    pub(crate) fn apply(&self, encoder: &mut wgpu::CommandEncoder, output_view: &wgpu::TextureView) {
+        let targets = self.targets.borrow();
+
+        for (i, pass) in self.passes.iter().enumerate() {
+            // The first pass reads the scene, the next ones ping-pong between the intermediate targets
+            let source = if i == 0 { &targets[0] } else { &targets[1 + (i - 1) % 2] };
+            let is_last = i + 1 == self.passes.len();
+            let destination = if is_last {
+                output_view
+            }
+            else {
+                &targets[1 + i % 2].view
+            };
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Post process render pass"),
+                color_attachments: &[
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: destination,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: wgpu::StoreOp::Store,
+                        },
+                        depth_slice: None,
+                    })
+                ],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            if is_last {
+                render_pass.set_pipeline(&pass.output_pipeline);
+                render_pass.set_bind_group(1, &pass.output_params_bind_group, &[]);
+            }
+            else {
+                render_pass.set_pipeline(&pass.intermediate_pipeline);
+                render_pass.set_bind_group(1, &pass.intermediate_params_bind_group, &[]);
+            }
+            render_pass.set_bind_group(0, &source.bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+
+    fn ensure_targets(&self, context: &GraphicsContext, width: u32, height: u32) {
+        let size = (width, height);
+
+        if self.targets_size.get() == size && !self.targets.borrow().is_empty() {
+            return;
+        }
+
+        log::info!("Creating post process targets {}x{}", size.0, size.1);
+
+        let intermediate_count = self.passes.len().saturating_sub(1).min(2);
+
+        let targets = std::iter::once(self.create_target(context, size.0, size.1, context.config.format))
+            .chain((0..intermediate_count).map(|_| self.create_target(context, size.0, size.1, INTERMEDIATE_FORMAT)))
+            .collect();
+
+        self.targets.replace(targets);
+        self.targets_size.set(size);
+    }
+
+    fn create_pipeline(
+        context: &GraphicsContext,
+        label: &str,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            })
+        })
+    }
+
+    fn create_target(&self, context: &GraphicsContext, width: u32, height: u32, format: wgpu::TextureFormat) -> PostProcessTarget {
+        let texture = context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Post process target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&Default::default());
+
+        let bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Post process source bind group"),
+            layout: &self.source_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler)
+                }
+            ],
+        });
+
+        PostProcessTarget {
+            view,
+            bind_group,
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{assets::texture::Texture2D, graphics::GraphicsContext};
+
+
+type PassFn<'a> = Box<dyn FnOnce(&GraphicsContext, &mut wgpu::CommandEncoder, &PassResources) + 'a>;
+
+pub struct PassResources<'r> {
+    output: &'r wgpu::TextureView,
+    output_size: (u32, u32),
+    surface_output: bool,
+    targets: &'r HashMap<String, Texture2D>,
+}
+
+impl PassResources<'_> {
+    pub fn output(&self) -> &wgpu::TextureView {
+        self.output
+    }
+
+    pub fn output_size(&self) -> (u32, u32) {
+        self.output_size
+    }
+
+    pub fn is_surface_output(&self) -> bool {
+        self.surface_output
+    }
+
+    // Transient target written by an earlier pass, only the declared inputs are guaranteed to be ready
+    pub fn input(&self, name: &str) -> Option<&Texture2D> {
+        self.targets.get(name)
+    }
+}
+
+pub struct RenderGraphPass<'a> {
+    name: String,
+    inputs: Vec<String>,
+    output: String,
+    output_size: Option<(u32, u32)>,
+    run: PassFn<'a>,
+}
+
+impl<'a> RenderGraphPass<'a> {
+    // `output` is either `RenderGraph::SURFACE` or the name of a transient target owned by the graph
+    pub fn new(
+        name: impl Into<String>,
+        output: impl Into<String>,
+        run: impl FnOnce(&GraphicsContext, &mut wgpu::CommandEncoder, &PassResources) + 'a
+    ) -> Self {
+        Self {
+            name: name.into(),
+            inputs: vec![],
+            output: output.into(),
+            output_size: None,
+            run: Box::new(run),
+        }
+    }
+
+    pub fn with_input(mut self, name: impl Into<String>) -> Self {
+        self.inputs.push(name.into());
+        self
+    }
+
+    // Transient targets default to the surface size
+    pub fn with_output_size(mut self, width: u32, height: u32) -> Self {
+        self.output_size = Some((width, height));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[derive(Debug)]
+pub enum RenderGraphError {
+    Cycle(Vec<String>),
+    SurfaceInput(String),
+    MissingInput { pass: String, input: String },
+    Surface(wgpu::SurfaceError),
+}
+
+impl std::fmt::Display for RenderGraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cycle(passes) => write!(f, "Render passes depend on each other: {}", passes.join(", ")),
+            Self::SurfaceInput(pass) => write!(f, "Render pass {pass} reads the surface, which can only be written"),
+            Self::MissingInput { pass, input } => write!(f, "Render pass {pass} reads {input} which no pass writes"),
+            Self::Surface(err) => write!(f, "Failed to acquire the surface texture: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for RenderGraphError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Surface(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<wgpu::SurfaceError> for RenderGraphError {
+    fn from(err: wgpu::SurfaceError) -> Self {
+        Self::Surface(err)
+    }
+}
+
+// Orders the passes of a frame from the textures they read and write. Passes run after the
+// writers of their inputs, writers of the same target run in registration order, and passes not
+// contributing to the surface are skipped. Transient targets are kept between frames while used
+#[derive(Default)]
+pub struct RenderGraph {
+    targets: HashMap<String, Texture2D>,
+}
+
+impl RenderGraph {
+    pub const SURFACE: &'static str = "surface";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Runs the passes in one command encoder then presents the frame
+    pub fn execute<'a>(&mut self, context: &GraphicsContext, passes: impl IntoIterator<Item = RenderGraphPass<'a>>) -> Result<(), RenderGraphError> {
+        let passes = passes.into_iter().collect::<Vec<_>>();
+        let order = Self::resolve_order(&passes)?;
+
+        let surface_size = (context.config.width, context.config.height);
+        self.allocate_targets(context, &passes, &order, surface_size);
+
+        let output = context.surface.get_current_texture()?;
+        let surface_view = output.texture.create_view(&Default::default());
+
+        let mut encoder = context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render graph commands encoder"),
+        });
+
+        let mut passes = passes.into_iter().map(Some).collect::<Vec<_>>();
+
+        for index in order {
+            let pass = passes[index].take().unwrap();
+            let surface_output = pass.output == Self::SURFACE;
+
+            let (view, output_size) = if surface_output {
+                (&surface_view, surface_size)
+            }
+            else {
+                let target = &self.targets[&pass.output];
+                (&target.view, (target.width, target.height))
+            };
+
+            let resources = PassResources {
+                output: view,
+                output_size,
+                surface_output,
+                targets: &self.targets,
+            };
+
+            (pass.run)(context, &mut encoder, &resources);
+        }
+
+        context.queue.submit(std::iter::once(encoder.finish()));
+
+        context.render_overlays(&output.texture);
+        context.capture_pending_screenshot(&output.texture);
+        output.present();
+
+        Ok(())
+    }
+
+    fn allocate_targets(&mut self, context: &GraphicsContext, passes: &[RenderGraphPass], order: &[usize], surface_size: (u32, u32)) {
+        let mut sizes = HashMap::new();
+
+        for pass in order.iter().map(|&index| &passes[index]).filter(|pass| pass.output != Self::SURFACE) {
+            let (width, height) = pass.output_size.unwrap_or(surface_size);
+            let size = (width.max(1), height.max(1));
+
+            if let Some(previous) = sizes.insert(pass.output.as_str(), size) && previous != size {
+                log::warn!("Render target {} is written with different sizes, using {}x{}", pass.output, size.0, size.1);
+            }
+        }
+
+        self.targets.retain(|name, texture| sizes.get(name.as_str()) == Some(&(texture.width, texture.height)));
+
+        for (name, (width, height)) in sizes {
+            if !self.targets.contains_key(name) {
+                log::info!("Creating render graph target {name} {width}x{height}");
+                self.targets.insert(name.to_owned(), Texture2D::new_render_target(context, name, width, height));
+            }
+        }
+    }
+
+    fn resolve_order(passes: &[RenderGraphPass]) -> Result<Vec<usize>, RenderGraphError> {
+        let mut writers = HashMap::<&str, Vec<usize>>::new();
+
+        for (index, pass) in passes.iter().enumerate() {
+            writers.entry(pass.output.as_str()).or_default().push(index);
+        }
+
+        let mut dependencies = vec![vec![]; passes.len()];
+
+        for (index, pass) in passes.iter().enumerate() {
+            for input in &pass.inputs {
+                if input == Self::SURFACE {
+                    return Err(RenderGraphError::SurfaceInput(pass.name.clone()));
+                }
+
+                if *input == pass.output {
+                    return Err(RenderGraphError::Cycle(vec![pass.name.clone()]));
+                }
+
+                let Some(input_writers) = writers.get(input.as_str()) else {
+                    return Err(RenderGraphError::MissingInput { pass: pass.name.clone(), input: input.clone() });
+                };
+
+                dependencies[index].extend(input_writers.iter().copied());
+            }
+
+            dependencies[index].extend(writers[pass.output.as_str()].iter().copied().filter(|&writer| writer < index));
+        }
+
+        let mut needed = HashSet::new();
+        let mut stack = writers.get(Self::SURFACE).cloned().unwrap_or_default();
+
+        while let Some(index) = stack.pop() {
+            if needed.insert(index) {
+                stack.extend(dependencies[index].iter().copied());
+            }
+        }
+
+        for (_, pass) in passes.iter().enumerate().filter(|(index, _)| !needed.contains(index)) {
+            log::debug!("Render pass {} does not contribute to the surface, skipping it", pass.name);
+        }
+
+        let mut order = Vec::with_capacity(needed.len());
+        let mut done = HashSet::new();
+
+        while order.len() < needed.len() {
+            let next = (0..passes.len())
+                .find(|index| needed.contains(index) && !done.contains(index) && dependencies[*index].iter().all(|dependency| done.contains(dependency)));
+
+            let Some(next) = next else {
+                let remaining = (0..passes.len())
+                    .filter(|index| needed.contains(index) && !done.contains(index))
+                    .map(|index| passes[index].name.clone())
+                    .collect();
+
+                return Err(RenderGraphError::Cycle(remaining));
+            };
+
+            done.insert(next);
+            order.push(next);
+        }
+
+        Ok(order)
+    }
+}
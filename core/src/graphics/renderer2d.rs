@@ -1,17 +1,27 @@
-use std::{cell::{Cell, RefCell}, collections::HashMap};
+use std::{cell::{Cell, Ref, RefCell}, collections::HashMap, marker::PhantomData};
 
 use wgpu::{include_wgsl, util::DeviceExt};
 
-use crate::{assets::{texture::{Texture2D, Texture2DCoordinates}, AssetHandle, AssetsManagerRef}, graphics::{camera::{Camera2D, CameraUniform}, shapes::Quad, GraphicsContext}};
+use crate::{assets::{texture::{Texture2D, Texture2DCoordinates}, AssetHandle, AssetsManagerRef}, graphics::{camera::{Camera2D, CameraUniform}, color::ClearColor, parallax::ParallaxLayer, post_process::PostProcessChain, render_graph::RenderGraphPass, renderer2d::retained::{RetainedBatch, RetainedBatchHandle}, shapes::Quad, text::{self, BitmapFont, TextOverflow}, letterbox_rect, GraphicsContext}};
 
+pub mod retained;
+
+// Mesh vertex in the unit square space of the instance quad, the quad transform maps (0, 0) to its
+// position and (1, 1) to its opposite corner
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
-struct Vertex {
-    position: [f32; 2],
-    tex_coords: [f32; 2],
+pub struct Vertex {
+    pub position: [f32; 2],
+    pub tex_coords: [f32; 2],
 }
 
 impl Vertex {
+    pub fn new(position: glam::Vec2, tex_coords: glam::Vec2) -> Self {
+        Self {
+            position: position.into(),
+            tex_coords: tex_coords.into(),
+        }
+    }
 
     const ATTRIBS: [wgpu::VertexAttribute; 2] =
         wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2];
@@ -28,27 +38,112 @@ impl Vertex {
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
-struct QuadInstanceData {
+pub struct QuadInstanceData {
+    pub model: glam::Mat4,
+    pub color: [f32; 4],
+    pub tex_coords_size: [f32; 2],
+    pub tex_coords_offset: [f32; 2],
+}
+
+impl QuadInstanceData {
+    fn new(model: glam::Mat4, color: glam::Vec4, atlas_coords: Texture2DCoordinates, flip: Flip) -> Self {
+        let mut tex_coords_offset = atlas_coords.offset;
+        let mut tex_coords_size = atlas_coords.size;
+
+        if flip.horizontal {
+            tex_coords_offset[0] += tex_coords_size[0];
+            tex_coords_size[0] = -tex_coords_size[0];
+        }
+
+        if flip.vertical {
+            tex_coords_offset[1] += tex_coords_size[1];
+            tex_coords_size[1] = -tex_coords_size[1];
+        }
+
+        Self {
+            model,
+            color: color.into(),
+            tex_coords_offset,
+            tex_coords_size,
+        }
+    }
+}
+
+impl InstanceData for QuadInstanceData {
+    const ATTRIBS: &'static [wgpu::VertexAttribute] =
+        &wgpu::vertex_attr_array![2 => Float32x4, 3 => Float32x4, 4 => Float32x4, 5 => Float32x4, 6 => Float32x4, 7 => Float32x2, 8 => Float32x2];
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct GradientInstanceData {
+    model: glam::Mat4,
+    colors: [[f32; 4]; 4],
+}
+
+impl InstanceData for GradientInstanceData {
+    const ATTRIBS: &'static [wgpu::VertexAttribute] =
+        &wgpu::vertex_attr_array![2 => Float32x4, 3 => Float32x4, 4 => Float32x4, 5 => Float32x4, 6 => Float32x4, 7 => Float32x4, 8 => Float32x4, 9 => Float32x4];
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct SdfInstanceData {
+    model: glam::Mat4,
+    color: [f32; 4],
+    // Quad size, corner radius and edge softness in pixels
+    shape: [f32; 4],
+}
+
+impl InstanceData for SdfInstanceData {
+    const ATTRIBS: &'static [wgpu::VertexAttribute] =
+        &wgpu::vertex_attr_array![2 => Float32x4, 3 => Float32x4, 4 => Float32x4, 5 => Float32x4, 6 => Float32x4, 7 => Float32x4];
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct SdfTextInstanceData {
     model: glam::Mat4,
     color: [f32; 4],
     tex_coords_size: [f32; 2],
     tex_coords_offset: [f32; 2],
+    // In pixels
+    edge_softness: f32,
+    _padding: [f32; 3],
 }
 
-impl QuadInstanceData {
+impl InstanceData for SdfTextInstanceData {
+    const ATTRIBS: &'static [wgpu::VertexAttribute] =
+        &wgpu::vertex_attr_array![2 => Float32x4, 3 => Float32x4, 4 => Float32x4, 5 => Float32x4, 6 => Float32x4, 7 => Float32x2, 8 => Float32x2, 9 => Float32];
+}
 
-    const ATTRIBS: [wgpu::VertexAttribute; 7] =
-        wgpu::vertex_attr_array![2 => Float32x4, 3 => Float32x4, 4 => Float32x4, 5 => Float32x4, 6 => Float32x4, 7 => Float32x2, 8 => Float32x2];
+// Per instance data of a custom material, locations 0 and 1 are taken by the quad vertex
+// position and texture coordinates so the instance attributes start at location 2
+pub trait InstanceData: bytemuck::Pod {
+    const ATTRIBS: &'static [wgpu::VertexAttribute];
 
     fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
             step_mode: wgpu::VertexStepMode::Instance,
-            attributes: &Self::ATTRIBS,
+            attributes: Self::ATTRIBS,
         }
     }
 }
 
+pub struct InstanceMaterial<T> {
+    index: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Clone for InstanceMaterial<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for InstanceMaterial<T> {}
+
 const QUAD: &[Vertex] = &[
     Vertex { position: [0.0, 0.0], tex_coords: [0.0, 0.0] },
     Vertex { position: [0.0, 1.0], tex_coords: [0.0, 1.0] },
@@ -62,285 +157,2359 @@ const QUAD_INDICES: &[u16] = &[
 ];
 
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MeshId(usize);
+
+impl MeshId {
+    // The built-in quad every draw call uses unless a mesh is given
+    pub const QUAD: MeshId = MeshId(0);
+}
+
+struct Mesh {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_format: wgpu::IndexFormat,
+    index_count: u32,
+    // Local space bounds used by viewport culling
+    bounds: (glam::Vec2, glam::Vec2),
+}
+
+impl Mesh {
+    fn new(context: &GraphicsContext, vertices: &[Vertex], indices: &[u8], index_format: wgpu::IndexFormat, index_count: u32) -> Self {
+        let bounds = vertices.iter()
+            .map(|vertex| glam::Vec2::from(vertex.position))
+            .fold((glam::Vec2::MAX, glam::Vec2::MIN), |(min, max), position| (min.min(position), max.max(position)));
+
+        Self {
+            vertex_buffer: context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Renderer2D mesh vertex buffer"),
+                contents: bytemuck::cast_slice(vertices),
+                usage: wgpu::BufferUsages::VERTEX
+            }),
+            index_buffer: context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Renderer2D mesh index buffer"),
+                contents: indices,
+                usage: wgpu::BufferUsages::INDEX
+            }),
+            index_format,
+            index_count,
+            bounds,
+        }
+    }
+}
+
+// Geometry and mask of a pushed instance, the default is an unmasked quad
+#[derive(Clone, Copy, Default)]
+struct InstanceShape {
+    mesh: MeshId,
+    mask: Option<AssetHandle<Texture2D>>,
+}
+
+// Draws the mesh once per instance, in chunks of one draw call when the instances are over the bucket limit
+#[derive(Clone, Copy)]
+struct InstancedDraw {
+    index_count: u32,
+    max_instances_per_bucket: Option<usize>,
+}
+
+impl InstancedDraw {
+    fn draw_calls(max_instances_per_bucket: Option<usize>, instances: usize) -> usize {
+        match max_instances_per_bucket {
+            Some(max) => instances.div_ceil(max.max(1)),
+            None => 1,
+        }
+    }
+
+    fn draw(self, render_pass: &mut wgpu::RenderPass, instances: usize) {
+        let chunk = self.max_instances_per_bucket.unwrap_or(instances).max(1);
+
+        for start in (0..instances).step_by(chunk) {
+            let end = (start + chunk).min(instances);
+            render_pass.draw_indexed(0..self.index_count, 0, start as u32..end as u32);
+        }
+    }
+}
+
+trait InstanceSource {
+    fn submit_to_render_pass(&self, context: &GraphicsContext, render_pass: &mut wgpu::RenderPass, draw: InstancedDraw);
+}
+
+// Instances are kept as raw bytes so built-in and custom material buckets share the same storage
 struct QuadsInstanceDataBuffer {
-    quads: Vec<QuadInstanceData>,
+    quads: Vec<u8>,
+    stride: usize,
+    // Only filled when a sort key function is set on the renderer
+    sort_keys: Vec<f32>,
+    // Kept between frames so sorting does not allocate once they reached the bucket size
+    sort_order: RefCell<Vec<usize>>,
+    sorted_quads: RefCell<Vec<u8>>,
     instance_buffer: RefCell<Option<wgpu::Buffer>>,
     buffer_len: Cell<usize>,
+    submitted_len: Cell<usize>,
+    // Largest upload of the current frame, a buffer submitted by several cameras counts once
+    frame_peak_len: Cell<usize>,
+    shrink: ShrinkHysteresis,
+}
+
+// Counts the consecutive frames an instance buffer stayed underused, advanced once per frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ShrinkHysteresis {
+    shrink_after_frames: Option<u32>,
+    underused_frames: u32,
+}
+
+impl ShrinkHysteresis {
+    // The GPU buffer is considered underused below a quarter of its size
+    const UNDERUSED_RATIO: usize = 4;
+
+    fn new(shrink_after_frames: Option<u32>) -> Self {
+        Self {
+            shrink_after_frames,
+            underused_frames: 0,
+        }
+    }
+
+    // `used` is the largest size uploaded during the frame and `buffer_len` the GPU buffer size,
+    // 0 when there is no buffer. True when the buffer should be freed
+    fn end_frame(&mut self, used: usize, buffer_len: usize) -> bool {
+        let Some(shrink_after_frames) = self.shrink_after_frames else {
+            return false;
+        };
+
+        if buffer_len == 0 || used >= buffer_len / Self::UNDERUSED_RATIO {
+            self.underused_frames = 0;
+            return false;
+        }
+
+        self.underused_frames += 1;
+
+        if self.underused_frames < shrink_after_frames {
+            return false;
+        }
+
+        self.underused_frames = 0;
+        true
+    }
 }
 
+// Debug builds can copy the instance buffers back for `Renderer2D::dump_instances`
+const INSTANCE_BUFFER_DEBUG_USAGE: wgpu::BufferUsages = if cfg!(debug_assertions) {
+    wgpu::BufferUsages::COPY_SRC
+}
+else {
+    wgpu::BufferUsages::empty()
+};
+
 impl QuadsInstanceDataBuffer {
-    fn new(quads_capacity: usize) -> Self {
-        let quads = Vec::with_capacity(quads_capacity);
+    fn new(quads_capacity: usize, stride: usize, shrink_after_frames: Option<u32>) -> Self {
+        let quads = Vec::with_capacity(quads_capacity * stride);
         Self {
             quads,
+            stride,
+            sort_keys: Vec::new(),
+            sort_order: RefCell::new(Vec::new()),
+            sorted_quads: RefCell::new(Vec::new()),
             instance_buffer: RefCell::new(None),
-            buffer_len: Cell::new(0)
+            buffer_len: Cell::new(0),
+            submitted_len: Cell::new(0),
+            frame_peak_len: Cell::new(0),
+            shrink: ShrinkHysteresis::new(shrink_after_frames),
         }
     }
 
     fn clear(&mut self) {
         self.quads.clear();
+        self.sort_keys.clear();
     }
 
-    fn push(&mut self, quad: QuadInstanceData) {
-        self.quads.push(quad);
+    fn len(&self) -> usize {
+        self.quads.len() / self.stride
     }
 
-    fn submit_to_render_pass(&self, context: &GraphicsContext, render_pass: &mut wgpu::RenderPass) {
-        if self.quads.is_empty() {
-            return;
-        }
+    fn push<T: InstanceData>(&mut self, quad: T) {
+        self.quads.extend_from_slice(bytemuck::bytes_of(&quad));
+    }
 
-        if self.instance_buffer.borrow().is_none() {
-            self.reallocate_instance_buffer(context);
-        }
-        else if self.buffer_len.get() < self.quads.len() {
-            log::info!("Destroying instance buffer");
-            self.instance_buffer.borrow().as_ref().unwrap().destroy();
-            self.reallocate_instance_buffer(context);
-        }
-        else {
-            context.queue.write_buffer(self.instance_buffer.borrow().as_ref().unwrap(), 0, bytemuck::cast_slice(&self.quads));
+    fn push_sort_key(&mut self, sort_key: f32) {
+        self.sort_keys.push(sort_key);
+    }
+
+    // The quads in sort key order, `None` when they are drawn in submission order
+    fn sorted_quads(&self) -> Option<Ref<'_, Vec<u8>>> {
+        if self.sort_keys.len() != self.len() {
+            return None;
         }
 
-        let instance_buffer = self.instance_buffer.borrow();
+        let mut order = self.sort_order.borrow_mut();
+        order.clear();
+        order.extend(0..self.len());
 
+        // Ties keep the submission order, the unstable sort does not allocate
+        order.sort_unstable_by(|a, b| self.sort_keys[*a].total_cmp(&self.sort_keys[*b]).then(a.cmp(b)));
 
-        render_pass.set_vertex_buffer(1, instance_buffer.as_ref().unwrap().slice(0..(self.quads.len() * std::mem::size_of::<QuadInstanceData>()) as _));
-        render_pass.draw_indexed(0..QUAD_INDICES.len() as _, 0, 0..self.quads.len() as _);
+        let mut sorted_quads = self.sorted_quads.borrow_mut();
+        sorted_quads.clear();
+        sorted_quads.extend(order.iter().flat_map(|index| &self.quads[index * self.stride..(index + 1) * self.stride]));
+        drop(sorted_quads);
+
+        Some(self.sorted_quads.borrow())
+    }
+
+    // Frees the GPU buffer once it stayed underused for enough frames, the next
+    // submit allocates a buffer fitting the current quads
+    fn end_frame(&mut self) {
+        let used = self.frame_peak_len.replace(0);
+
+        if self.shrink.end_frame(used, self.buffer_len.get()) {
+            log::info!("Shrinking instance buffer");
+
+            if let Some(instance_buffer) = self.instance_buffer.take() {
+                instance_buffer.destroy();
+            }
+
+            self.buffer_len.set(0);
+        }
     }
 
-    fn reallocate_instance_buffer(&self, context: &GraphicsContext) {
+    fn reallocate_instance_buffer(&self, context: &GraphicsContext, quads: &[u8]) {
         log::info!("Reallocating the instance buffer");
         let instance_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: None,
-                contents: bytemuck::cast_slice(&self.quads),
-                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                contents: quads,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST | INSTANCE_BUFFER_DEBUG_USAGE,
         });
 
         self.instance_buffer.replace(Some(instance_buffer));
         self.buffer_len.set(self.quads.len());
     }
-}
 
-pub struct Renderer2D {
-    render_pipeline: wgpu::RenderPipeline,
-    assets_manager: AssetsManagerRef,
-    clear_color: wgpu::Color,
+    // Reads the instances uploaded by the last submit back from the GPU
+    #[cfg(debug_assertions)]
+    fn read_back(&self, context: &GraphicsContext) -> Vec<u8> {
+        let instance_buffer = self.instance_buffer.borrow();
+        let size = self.submitted_len.get() as wgpu::BufferAddress;
 
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
+        let Some(instance_buffer) = instance_buffer.as_ref().filter(|_| size > 0) else {
+            return Vec::new();
+        };
 
-    camera_uniform: Option<CameraUniform>,
-    camera_buffer: wgpu::Buffer,
-    camera_bind_group_layout: wgpu::BindGroupLayout,
-    
-    white_texture: AssetHandle<Texture2D>,
-    quads_instances: HashMap<AssetHandle<Texture2D>, QuadsInstanceDataBuffer>,
+        let staging_buffer = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Renderer2D instances read back buffer"),
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Renderer2D instances read back encoder"),
+        });
+
+        encoder.copy_buffer_to_buffer(instance_buffer, 0, &staging_buffer, 0, size);
+        context.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = staging_buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| ());
+
+        if let Err(err) = context.device.poll(wgpu::PollType::Wait) {
+            log::error!("Failed to read back the instance buffer: {err}");
+            return Vec::new();
+        }
+
+        let data = slice.get_mapped_range().to_vec();
+        staging_buffer.unmap();
+
+        data
+    }
 }
 
+impl InstanceSource for QuadsInstanceDataBuffer {
+    fn submit_to_render_pass(&self, context: &GraphicsContext, render_pass: &mut wgpu::RenderPass, draw: InstancedDraw) {
+        self.frame_peak_len.set(self.frame_peak_len.get().max(self.quads.len()));
 
-impl Renderer2D {
+        if self.quads.is_empty() {
+            return;
+        }
 
-    const MAX_QUAD: usize = 1_000_00;
+        let sorted_quads = self.sorted_quads();
+        let quads = sorted_quads.as_deref().map_or(self.quads.as_slice(), Vec::as_slice);
 
-    pub fn new(context: &GraphicsContext, assets_manager: AssetsManagerRef) -> Self {
-        let shader = context.device
-                .create_shader_module(include_wgsl!("../../assets/shaders/shader_quad.wgsl"));
+        if self.instance_buffer.borrow().is_none() {
+            self.reallocate_instance_buffer(context, quads);
+        }
+        else if self.buffer_len.get() < self.quads.len() {
+            log::info!("Destroying instance buffer");
+            self.instance_buffer.borrow().as_ref().unwrap().destroy();
+            self.reallocate_instance_buffer(context, quads);
+        }
+        else {
+            context.queue.write_buffer(self.instance_buffer.borrow().as_ref().unwrap(), 0, quads);
+        }
 
+        self.submitted_len.set(quads.len());
 
-        
-        let camera_bind_group_layout = context.device
-                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    label: Some("Renderer2D bind group layout"),
-                    entries: &[
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 0,
-                            visibility: wgpu::ShaderStages::VERTEX,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Uniform,
-                                has_dynamic_offset: false,
-                                min_binding_size: None,
-                            },
-                            count: None,
-                        }
-                    ],
-                });
+        let instance_buffer = self.instance_buffer.borrow();
 
-        let render_pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Renderer2D pipeline layout"),
-            bind_group_layouts: &[
-                &camera_bind_group_layout,
-                &Texture2D::create_bind_group_layout(context)
-            ],
-            push_constant_ranges: &[],
-        });
 
-        let render_pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render2D pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                compilation_options: Default::default(),
-                buffers: &[
-                    Vertex::desc(),
-                    QuadInstanceData::desc()
-                ],
-            },
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None,
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                compilation_options: Default::default(),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: context.config.format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            })
-        });
+        render_pass.set_vertex_buffer(1, instance_buffer.as_ref().unwrap().slice(0..self.quads.len() as _));
+        draw.draw(render_pass, self.len());
+    }
+}
 
+// Camera of a render pass, a `submit_view` camera replaces the one of the last `begin`. `None` when
+// neither is set, the pass then only clears its target
+fn pass_camera_uniform(active: Option<CameraUniform>, view_camera: Option<&Camera2D>) -> Option<CameraUniform> {
+    view_camera.map(|camera| CameraUniform::from_matrix(camera.to_matrix())).or(active)
+}
 
-        let camera_buffer = context.device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Renderer2D camera buffer"),
-            size: std::mem::size_of::<CameraUniform>() as wgpu::BufferAddress,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+fn snap_to_pixel_grid(translation: glam::Vec2, pixels_per_unit: glam::Vec2) -> glam::Vec2 {
+    (translation * pixels_per_unit).round() / pixels_per_unit
+}
 
-        let mut assets_mgr_lock = assets_manager.lock().unwrap();
-        let white_texture = assets_mgr_lock.store_asset(
-            Texture2D::from_memory(context, "dymm", &[255, 255, 255, 255], 1, 1)
-        );
-        drop(assets_mgr_lock);
+// Maps the z index into the [0, 1] depth range of the camera projection, higher z indices are closer
+fn z_index_to_depth(z_index: i32) -> f32 {
+    const Z_RANGE: i32 = 1 << 16;
 
-        Self {
-            render_pipeline,
-            clear_color: wgpu::Color {r: 0.1, g: 0.1, b: 0.2, a: 1.0},
-            vertex_buffer: Self::create_vertex_buffer(context),
-            index_buffer: Self::create_index_buffer(context),
+    0.5 - z_index.clamp(-Z_RANGE, Z_RANGE) as f32 / (2.0 * Z_RANGE as f32 + 2.0)
+}
 
-            camera_buffer,
-            camera_uniform: None,
-            camera_bind_group_layout,
-            
-            assets_manager,
+fn clamp_edge_softness(pixels: f32) -> f32 {
+    pixels.max(0.0)
+}
 
-            quads_instances: HashMap::new(),
-            white_texture,
-        }
+fn quad_transform(quad: &Quad, params: &DrawParams) -> glam::Mat4 {
+    match params.pivot {
+        Some(pivot) => pivot_transform(quad, pivot),
+        None => quad.get_transform(),
     }
+}
 
-    pub fn begin(&mut self, clear_color: wgpu::Color, camera: &Camera2D) {
+fn pivot_transform(quad: &Quad, pivot: glam::Vec2) -> glam::Mat4 {
+    let rotation = glam::Quat::from_rotation_z(quad.rotation().to_radians());
 
-        self.clear_color = clear_color;
+    glam::Mat4::from_rotation_translation(rotation, pivot.extend(0.0))
+        * glam::Mat4::from_translation((quad.position() - pivot).extend(0.0))
+        * glam::Mat4::from_scale(quad.size().extend(1.0))
+}
 
-        self.camera_uniform = Some(CameraUniform::from_matrix(camera.to_matrix()));
-        self.quads_instances.values_mut().for_each(QuadsInstanceDataBuffer::clear);
+fn is_degenerate_transform(model: &glam::Mat4) -> bool {
+    let area = glam::Mat2::from_cols(model.x_axis.truncate().truncate(), model.y_axis.truncate().truncate()).determinant();
+
+    !model.is_finite() || area == 0.0
+}
+
+fn transformed_aabb(model: &glam::Mat4, (min, max): (glam::Vec2, glam::Vec2)) -> (glam::Vec2, glam::Vec2) {
+    [min, glam::vec2(min.x, max.y), max, glam::vec2(max.x, min.y)].into_iter()
+        .map(|corner| model.transform_point3(corner.extend(0.0)).truncate())
+        .fold((glam::Vec2::MAX, glam::Vec2::MIN), |(min, max), corner| (min.min(corner), max.max(corner)))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct BucketKey {
+    // `None` for solid colors, drawn with the renderer white texture
+    texture: Option<AssetHandle<Texture2D>>,
+    z_index: i32,
+    sampler: SamplerHandle,
+    opaque: bool,
+    blend: BlendMode,
+    stencil: StencilMode,
+    // Custom material index, `None` for the built-in quad pipelines
+    material: Option<usize>,
+    mask: Option<AssetHandle<Texture2D>>,
+    mesh: MeshId,
+}
+
+impl BucketKey {
+    fn pipeline_key(&self) -> (bool, BlendMode, StencilTest, Option<usize>, bool) {
+        (self.opaque, self.blend, self.stencil.test(), self.material, self.mask.is_some())
     }
+}
 
-    pub fn draw_quad(&mut self, quad: &Quad) {
-        self.draw_quad_textured(quad, self.white_texture, Default::default());
+struct QuadsBuckets {
+    buckets: Vec<(BucketKey, QuadsInstanceDataBuffer)>,
+    indices: HashMap<BucketKey, usize>,
+    shrink_after_frames: Option<u32>,
+}
+
+impl QuadsBuckets {
+    fn new() -> Self {
+        Self {
+            buckets: Vec::new(),
+            indices: HashMap::new(),
+            shrink_after_frames: Some(300),
+        }
     }
 
-    pub fn draw_quad_textured(&mut self, quad: &Quad, texture_handle: AssetHandle<Texture2D>, atlas_coords: Texture2DCoordinates) {
-        let quads = 
-                self
-                .quads_instances
-                .entry(texture_handle)
-                .or_insert_with(|| QuadsInstanceDataBuffer::new(Self::MAX_QUAD))
-                ;
-
-        quads.push(QuadInstanceData {
-            model: quad.get_transform(),
-            color: quad.color.into(),
-            tex_coords_offset: atlas_coords.offset,
-            tex_coords_size: atlas_coords.size
-        });
+    fn set_shrink_after_frames(&mut self, shrink_after_frames: Option<u32>) {
+        self.shrink_after_frames = shrink_after_frames;
+        self.buckets.iter_mut().for_each(|(_, quads)| quads.shrink = ShrinkHysteresis::new(shrink_after_frames));
     }
 
-    pub fn submit(&self, context: &GraphicsContext) -> Result<(), wgpu::SurfaceError> {
-        let output = context.surface.get_current_texture()?;
-        let view = output.texture.create_view(&Default::default());
+    fn end_frame(&mut self) {
+        self.buckets.iter_mut().for_each(|(_, quads)| quads.end_frame());
+    }
 
-        let mut encoder = context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Renderer2D commands encoder"),
+    fn get_or_insert(&mut self, key: BucketKey, quads_capacity: usize, stride: usize) -> &mut QuadsInstanceDataBuffer {
+        let index = *self.indices.entry(key).or_insert_with(|| {
+            self.buckets.push((key, QuadsInstanceDataBuffer::new(quads_capacity, stride, self.shrink_after_frames)));
+            self.buckets.len() - 1
         });
 
+        &mut self.buckets[index].1
+    }
 
-        self.start_render_pass(context, &mut encoder, &view);
+    fn clear(&mut self) {
+        self.buckets.iter_mut().for_each(|(_, quads)| quads.clear());
+    }
 
-        context.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
-        Ok(())
+    fn iter(&self) -> impl Iterator<Item = &(BucketKey, QuadsInstanceDataBuffer)> {
+        self.buckets.iter()
     }
 
+    fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    fn get(&self, index: usize) -> &(BucketKey, QuadsInstanceDataBuffer) {
+        &self.buckets[index]
+    }
+}
+
+// Position of a bucket in the renderer storages, buckets are never removed so it stays valid
+#[derive(Clone, Copy)]
+enum BucketSlot {
+    Quads(usize),
+    Retained(usize),
+}
+
+// Side of the quad edges a border is drawn on, `Inside` keeps the bordered quad at the quad bounds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BorderPlacement {
+    #[default]
+    Inside,
+    Outside,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Flip {
+    pub horizontal: bool,
+    pub vertical: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TextureFilter {
+    #[default]
+    Linear,
+    Nearest,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BorderColor {
+    TransparentBlack,
+    OpaqueBlack,
+    OpaqueWhite,
+}
+
+impl From<BorderColor> for wgpu::SamplerBorderColor {
+    fn from(color: BorderColor) -> Self {
+        match color {
+            BorderColor::TransparentBlack => Self::TransparentBlack,
+            BorderColor::OpaqueBlack => Self::OpaqueBlack,
+            BorderColor::OpaqueWhite => Self::OpaqueWhite,
+        }
+    }
+}
+
+// Sampling outside of the texture coordinates. `ClampToBorder` needs the
+// `ADDRESS_MODE_CLAMP_TO_BORDER` device feature and falls back to `ClampToEdge` without it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TextureWrap {
+    #[default]
+    ClampToEdge,
+    Repeat,
+    MirrorRepeat,
+    ClampToBorder(BorderColor),
+}
+
+impl TextureWrap {
+    const ALL: [Self; 6] = [
+        Self::ClampToEdge,
+        Self::Repeat,
+        Self::MirrorRepeat,
+        Self::ClampToBorder(BorderColor::TransparentBlack),
+        Self::ClampToBorder(BorderColor::OpaqueBlack),
+        Self::ClampToBorder(BorderColor::OpaqueWhite),
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SamplerSettings {
+    pub filter: TextureFilter,
+    pub wrap: TextureWrap,
+    // Samples taken along the view slope, above 1 needs linear filtering. Clamped to [1, 16]
+    pub anisotropy: u16,
+}
+
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        Self {
+            filter: TextureFilter::default(),
+            wrap: TextureWrap::default(),
+            anisotropy: 1,
+        }
+    }
+}
+
+// Sampler created once on the renderer and selected per draw with `DrawParams::sampler`. Every
+// filter and wrap combination is built in, `Renderer2D::register_sampler` adds the other settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SamplerHandle(usize);
+
+impl SamplerHandle {
+    pub const LINEAR_CLAMP: Self = Self::builtin(TextureFilter::Linear, TextureWrap::ClampToEdge);
+    pub const NEAREST_CLAMP: Self = Self::builtin(TextureFilter::Nearest, TextureWrap::ClampToEdge);
+    pub const LINEAR_REPEAT: Self = Self::builtin(TextureFilter::Linear, TextureWrap::Repeat);
+    pub const NEAREST_REPEAT: Self = Self::builtin(TextureFilter::Nearest, TextureWrap::Repeat);
+    pub const LINEAR_MIRROR: Self = Self::builtin(TextureFilter::Linear, TextureWrap::MirrorRepeat);
+    pub const NEAREST_MIRROR: Self = Self::builtin(TextureFilter::Nearest, TextureWrap::MirrorRepeat);
+
+    const BUILTIN_FILTERS: [TextureFilter; 2] = [TextureFilter::Linear, TextureFilter::Nearest];
+
+    // Built-in samplers are registered in the order of `TextureWrap::ALL` then of the filters
+    pub const fn builtin(filter: TextureFilter, wrap: TextureWrap) -> Self {
+        let wrap_index = match wrap {
+            TextureWrap::ClampToEdge => 0,
+            TextureWrap::Repeat => 1,
+            TextureWrap::MirrorRepeat => 2,
+            TextureWrap::ClampToBorder(BorderColor::TransparentBlack) => 3,
+            TextureWrap::ClampToBorder(BorderColor::OpaqueBlack) => 4,
+            TextureWrap::ClampToBorder(BorderColor::OpaqueWhite) => 5,
+        };
+
+        let filter_index = match filter {
+            TextureFilter::Linear => 0,
+            TextureFilter::Nearest => 1,
+        };
+
+        Self(wrap_index * Self::BUILTIN_FILTERS.len() + filter_index)
+    }
+}
+
+// How the quad color is combined with what is already drawn
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BlendMode {
+    #[default]
+    Alpha,
+    Additive,
+    Multiply,
+}
+
+impl BlendMode {
+    const ALL: [Self; 3] = [Self::Alpha, Self::Additive, Self::Multiply];
+
+    fn blend_state(self) -> wgpu::BlendState {
+        match self {
+            Self::Alpha => wgpu::BlendState::ALPHA_BLENDING,
+            Self::Additive => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            },
+            Self::Multiply => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Dst,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            },
+        }
+    }
+}
+
+// Stencil use of a draw, only available with `Renderer2D::with_stencil`. Quads writing the
+// stencil must be drawn before the ones testing it, give them a lower z index
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum StencilMode {
+    #[default]
+    Disabled,
+    // Replaces the stencil value of every covered pixel, transparent texels included
+    Write(u8),
+    Equal(u8),
+    NotEqual(u8),
+}
+
+impl StencilMode {
+    fn reference(self) -> u32 {
+        match self {
+            Self::Disabled => 0,
+            Self::Write(reference) | Self::Equal(reference) | Self::NotEqual(reference) => reference as u32,
+        }
+    }
+
+    fn test(self) -> StencilTest {
+        match self {
+            Self::Disabled => StencilTest::Disabled,
+            Self::Write(_) => StencilTest::Write,
+            Self::Equal(_) => StencilTest::Equal,
+            Self::NotEqual(_) => StencilTest::NotEqual,
+        }
+    }
+}
+
+// Stencil state baked in a pipeline, the reference value is set per bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum StencilTest {
+    Disabled,
+    Write,
+    Equal,
+    NotEqual,
+}
+
+impl StencilTest {
+    const ALL: [Self; 4] = [Self::Disabled, Self::Write, Self::Equal, Self::NotEqual];
+
+    fn stencil_state(self) -> wgpu::StencilState {
+        let (compare, pass_op, write_mask) = match self {
+            Self::Disabled => return wgpu::StencilState::default(),
+            Self::Write => (wgpu::CompareFunction::Always, wgpu::StencilOperation::Replace, 0xff),
+            Self::Equal => (wgpu::CompareFunction::Equal, wgpu::StencilOperation::Keep, 0),
+            Self::NotEqual => (wgpu::CompareFunction::NotEqual, wgpu::StencilOperation::Keep, 0),
+        };
+
+        let face = wgpu::StencilFaceState {
+            compare,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op,
+        };
+
+        wgpu::StencilState {
+            front: face,
+            back: face,
+            read_mask: 0xff,
+            write_mask,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct PipelineState {
+    depth_format: wgpu::TextureFormat,
+    // `None` for the opaque pipeline, drawn without blending and writing depth
+    blend: Option<BlendMode>,
+    stencil: StencilTest,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DrawParams {
+    pub tint: glam::Vec4,
+    pub z_index: i32,
+    pub flip: Flip,
+    pub filter: TextureFilter,
+    pub wrap: TextureWrap,
+    // Replaces `filter` and `wrap` when set
+    pub sampler: Option<SamplerHandle>,
+    // Opaque quads are drawn without blending and ordered by the depth buffer,
+    // only set it when every texel of the quad is fully opaque. Ignored for non alpha blend modes
+    pub opaque: bool,
+    pub blend: BlendMode,
+    pub stencil: StencilMode,
+    // World space point the quad rotation is applied around instead of the quad center, the quad
+    // itself is not changed. Used by the quad, sprite, gradient and shape draws
+    pub pivot: Option<glam::Vec2>,
+}
+
+impl Default for DrawParams {
+    fn default() -> Self {
+        Self {
+            tint: glam::Vec4::ONE,
+            z_index: 0,
+            flip: Flip::default(),
+            filter: TextureFilter::default(),
+            wrap: TextureWrap::default(),
+            sampler: None,
+            opaque: false,
+            blend: BlendMode::default(),
+            stencil: StencilMode::default(),
+            pivot: None,
+        }
+    }
+}
+
+impl DrawParams {
+    fn sampler_handle(&self) -> SamplerHandle {
+        self.sampler.unwrap_or(SamplerHandle::builtin(self.filter, self.wrap))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderStats {
+    pub instances: usize,
+    pub culled_instances: usize,
+    pub draw_calls: usize,
+    // Chunks of the buckets split by `Renderer2D::set_instance_limit`, one draw call each and included
+    // in `draw_calls`
+    pub split_draw_calls: usize,
+    // Changes of pipeline between draw calls, buckets of different blend modes or materials need their own
+    pub pipeline_switches: usize,
+}
+
+#[derive(Debug)]
+pub enum Renderer2DError {
+    ShaderCompilation(wgpu::Error),
+    PipelineCreation(wgpu::Error),
+    // The assets manager mutex was poisoned by a panic while locked
+    AssetsManager,
+}
+
+impl std::fmt::Display for Renderer2DError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ShaderCompilation(err) => write!(f, "Failed to compile the renderer shaders: {err}"),
+            Self::PipelineCreation(err) => write!(f, "Failed to create the renderer pipelines: {err}"),
+            Self::AssetsManager => write!(f, "The assets manager is poisoned, the white texture cannot be stored"),
+        }
+    }
+}
+
+impl std::error::Error for Renderer2DError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::ShaderCompilation(err) | Self::PipelineCreation(err) => Some(err),
+            Self::AssetsManager => None,
+        }
+    }
+}
+
+// Fills a letterboxed viewport with the clear color, the load operation clears the whole surface
+struct ViewportClear {
+    pipeline: wgpu::RenderPipeline,
+    color_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl ViewportClear {
+    fn new(context: &GraphicsContext, depth_format: wgpu::TextureFormat) -> Self {
+        let shader = context.device
+                .create_shader_module(include_wgsl!("../../assets/shaders/viewport_clear.wgsl"));
+
+        let bind_group_layout = context.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Renderer2D viewport clear bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }
+            ],
+        });
+
+        let layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Renderer2D viewport clear pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Renderer2D viewport clear pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: context.config.format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            })
+        });
+
+        let color_buffer = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Renderer2D viewport clear color buffer"),
+            size: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Renderer2D viewport clear bind group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: color_buffer.as_entire_binding(),
+                }
+            ],
+        });
+
+        Self {
+            pipeline,
+            color_buffer,
+            bind_group,
+        }
+    }
+
+    fn draw(&self, context: &GraphicsContext, render_pass: &mut wgpu::RenderPass, color: wgpu::Color) {
+        let color = [color.r as f32, color.g as f32, color.b as f32, color.a as f32];
+        context.queue.write_buffer(&self.color_buffer, 0, bytemuck::cast_slice(&color));
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+struct Frame {
+    output: wgpu::SurfaceTexture,
+    view: wgpu::TextureView,
+    cleared: bool,
+}
+
+pub struct Renderer2D {
+    render_pipelines: HashMap<(BlendMode, StencilTest), wgpu::RenderPipeline>,
+    opaque_render_pipelines: HashMap<StencilTest, wgpu::RenderPipeline>,
+    masked_render_pipelines: HashMap<(BlendMode, StencilTest), wgpu::RenderPipeline>,
+    render_pipeline_layout: wgpu::PipelineLayout,
+    materials: Vec<wgpu::RenderPipeline>,
+    gradient_material: InstanceMaterial<GradientInstanceData>,
+    sdf_material: InstanceMaterial<SdfInstanceData>,
+    sdf_text_material: InstanceMaterial<SdfTextInstanceData>,
+    sdf_edge_softness: f32,
+    font: Option<BitmapFont>,
+    text_overflow: TextOverflow,
+    assets_manager: AssetsManagerRef,
+    clear_color: wgpu::Color,
+
+    meshes: Vec<Mesh>,
+
+    camera: Camera2D,
+    camera_uniform: Option<CameraUniform>,
+    camera_bind_group_layout: wgpu::BindGroupLayout,
+
+    sampler_bind_group_layout: wgpu::BindGroupLayout,
+    // Indexed by `SamplerHandle`, the built-in samplers first
+    samplers: Vec<wgpu::BindGroup>,
+    
+    // `white` shared with the assets manager for the texture draw methods, `None` for color-only renderers
+    white_texture: Option<AssetHandle<Texture2D>>,
+    // Bound for the solid color buckets. The quad pipelines all sample the texture group, binding a
+    // 1x1 texture keeps a single shader and pipeline set for textured and solid color quads, which
+    // batch the same way, instead of doubling every pipeline variant for an untextured shader
+    white: Texture2D,
+    named_textures: HashMap<String, Option<AssetHandle<Texture2D>>>,
+    quads_instances: QuadsBuckets,
+    retained_batches: Vec<RetainedBatch>,
+    // Draw order of every bucket, empty ones included. Sorted again only when buckets are added
+    bucket_order: RefCell<Vec<BucketSlot>>,
+
+    // Depth targets by size, the surface and render targets can be drawn in the same frame
+    depth_targets: RefCell<HashMap<(u32, u32), wgpu::TextureView>>,
+    depth_format: wgpu::TextureFormat,
+    depth_clear_value: f32,
+    stencil_clear_value: u32,
+    stencil_warned: bool,
+    viewport_clear: ViewportClear,
+
+    post_process: Option<PostProcessChain>,
+
+    surface_size: glam::Vec2,
+    pixels_per_unit: glam::Vec2,
+    pixel_snapping: bool,
+
+    sort_key: Option<fn(&Quad) -> f32>,
+
+    viewport_culling: bool,
+    visible_rect: Option<(glam::Vec2, glam::Vec2)>,
+    culled_instances: usize,
+
+    max_instances_per_bucket: Option<usize>,
+    // The instance limit warning is logged once per renderer
+    instance_limit_warned: bool,
+
+    world_target: Option<AssetHandle<Texture2D>>,
+    ui_visible_rect: Option<(glam::Vec2, glam::Vec2)>,
+
+    frame: Option<Frame>,
+    camera_pending: bool,
+}
+
+
+impl Renderer2D {
+
+    const MAX_QUAD: usize = 100_000;
+    const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    const DEPTH_STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+    const DEFAULT_SDF_EDGE_SOFTNESS: f32 = 1.0;
+
+    pub fn new(context: &GraphicsContext, assets_manager: AssetsManagerRef) -> Result<Self, Renderer2DError> {
+        Self::build(context, assets_manager, Self::DEPTH_FORMAT, true)
+    }
+
+    // Adds a stencil buffer to the depth buffer for the `DrawParams::stencil` modes
+    pub fn with_stencil(context: &GraphicsContext, assets_manager: AssetsManagerRef) -> Result<Self, Renderer2DError> {
+        Self::build(context, assets_manager, Self::DEPTH_STENCIL_FORMAT, true)
+    }
+
+    // The assets manager is not used at construction, the white texture is not stored in it and
+    // `white_texture` returns `None`. Solid colors are drawn with `draw_quad` and `draw_quad_ex`
+    pub fn color_only(context: &GraphicsContext, assets_manager: AssetsManagerRef) -> Result<Self, Renderer2DError> {
+        Self::build(context, assets_manager, Self::DEPTH_FORMAT, false)
+    }
+
+    fn build(context: &GraphicsContext, assets_manager: AssetsManagerRef, depth_format: wgpu::TextureFormat, store_white_texture: bool) -> Result<Self, Renderer2DError> {
+        // Validation errors are caught by error scopes instead of reaching the device error handler which panics
+        context.device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let shader = context.device
+                .create_shader_module(include_wgsl!("../../assets/shaders/shader_quad.wgsl"));
+
+        let gradient_shader = context.device
+                .create_shader_module(include_wgsl!("../../assets/shaders/shader_quad_gradient.wgsl"));
+
+        let sdf_shader = context.device
+                .create_shader_module(include_wgsl!("../../assets/shaders/shader_quad_sdf.wgsl"));
+
+        let sdf_text_shader = context.device
+                .create_shader_module(include_wgsl!("../../assets/shaders/shader_text_sdf.wgsl"));
+
+        if let Some(err) = smol::block_on(context.device.pop_error_scope()) {
+            return Err(Renderer2DError::ShaderCompilation(err));
+        }
+
+        context.device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        
+        let camera_bind_group_layout = context.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Renderer2D bind group layout"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::VERTEX,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        }
+                    ],
+                });
+
+        let sampler_bind_group_layout = context.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Renderer2D sampler bind group layout"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        }
+                    ],
+                });
+
+        let render_pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Renderer2D pipeline layout"),
+            bind_group_layouts: &[
+                &camera_bind_group_layout,
+                &Texture2D::create_bind_group_layout(context),
+                &sampler_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let masked_pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Renderer2D masked pipeline layout"),
+            bind_group_layouts: &[
+                &camera_bind_group_layout,
+                &Texture2D::create_bind_group_layout(context),
+                &sampler_bind_group_layout,
+                &Texture2D::create_bind_group_layout(context),
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let stencil_tests = if depth_format.has_stencil_aspect() {
+            StencilTest::ALL.as_slice()
+        }
+        else {
+            [StencilTest::Disabled].as_slice()
+        };
+
+        let blend_variants = BlendMode::ALL.into_iter()
+            .flat_map(|blend| stencil_tests.iter().map(move |stencil| (blend, *stencil)))
+            .collect::<Vec<_>>();
+
+        let render_pipelines = blend_variants.iter()
+            .map(|&(blend, stencil)| ((blend, stencil), Self::create_render_pipeline(
+                context,
+                &render_pipeline_layout,
+                &shader,
+                &format!("Render2D {blend:?} {stencil:?} pipeline"),
+                QuadInstanceData::desc(),
+                "fs_main",
+                PipelineState { depth_format, blend: Some(blend), stencil }
+            )))
+            .collect();
+
+        let opaque_render_pipelines = stencil_tests.iter()
+            .map(|&stencil| (stencil, Self::create_render_pipeline(
+                context,
+                &render_pipeline_layout,
+                &shader,
+                &format!("Render2D opaque {stencil:?} pipeline"),
+                QuadInstanceData::desc(),
+                "fs_main",
+                PipelineState { depth_format, blend: None, stencil }
+            )))
+            .collect();
+
+        let masked_render_pipelines = blend_variants.iter()
+            .map(|&(blend, stencil)| ((blend, stencil), Self::create_render_pipeline(
+                context,
+                &masked_pipeline_layout,
+                &shader,
+                &format!("Render2D masked {blend:?} {stencil:?} pipeline"),
+                QuadInstanceData::desc(),
+                "fs_main_masked",
+                PipelineState { depth_format, blend: Some(blend), stencil }
+            )))
+            .collect();
+
+
+        let gradient_render_pipeline = Self::create_render_pipeline(
+            context,
+            &render_pipeline_layout,
+            &gradient_shader,
+            "Render2D gradient pipeline",
+            GradientInstanceData::desc(),
+            "fs_main",
+            PipelineState { depth_format, blend: Some(BlendMode::Alpha), stencil: StencilTest::Disabled }
+        );
+
+        let sdf_render_pipeline = Self::create_render_pipeline(
+            context,
+            &render_pipeline_layout,
+            &sdf_shader,
+            "Render2D SDF pipeline",
+            SdfInstanceData::desc(),
+            "fs_main",
+            PipelineState { depth_format, blend: Some(BlendMode::Alpha), stencil: StencilTest::Disabled }
+        );
+
+        let sdf_text_render_pipeline = Self::create_render_pipeline(
+            context,
+            &render_pipeline_layout,
+            &sdf_text_shader,
+            "Render2D SDF text pipeline",
+            SdfTextInstanceData::desc(),
+            "fs_main",
+            PipelineState { depth_format, blend: Some(BlendMode::Alpha), stencil: StencilTest::Disabled }
+        );
+
+        let viewport_clear = ViewportClear::new(context, depth_format);
+
+        if let Some(err) = smol::block_on(context.device.pop_error_scope()) {
+            return Err(Renderer2DError::PipelineCreation(err));
+        }
+
+        let white = Texture2D::from_memory(context, "Renderer2D white texture", &[255, 255, 255, 255], 1, 1);
+
+        let white_texture = if store_white_texture {
+            let mut assets_mgr_lock = assets_manager.lock().map_err(|_| Renderer2DError::AssetsManager)?;
+            Some(assets_mgr_lock.store_asset(white.clone()))
+        }
+        else {
+            None
+        };
+
+        if !context.device.features().contains(wgpu::Features::ADDRESS_MODE_CLAMP_TO_BORDER) {
+            log::info!("ClampToBorder texture wrap is not supported by the device, ClampToEdge is used instead");
+        }
+
+        let samplers = TextureWrap::ALL.into_iter()
+            .flat_map(|wrap| SamplerHandle::BUILTIN_FILTERS.map(|filter| (filter, wrap)))
+            .map(|(filter, wrap)| {
+                let settings = SamplerSettings { filter, wrap, ..Default::default() };
+                Self::create_sampler_bind_group(context, &sampler_bind_group_layout, settings)
+            })
+            .collect();
+
+        Ok(Self {
+            render_pipelines,
+            opaque_render_pipelines,
+            masked_render_pipelines,
+            render_pipeline_layout,
+            materials: vec![gradient_render_pipeline, sdf_render_pipeline, sdf_text_render_pipeline],
+            gradient_material: InstanceMaterial { index: 0, _marker: PhantomData },
+            sdf_material: InstanceMaterial { index: 1, _marker: PhantomData },
+            sdf_text_material: InstanceMaterial { index: 2, _marker: PhantomData },
+            sdf_edge_softness: Self::DEFAULT_SDF_EDGE_SOFTNESS,
+            font: None,
+            text_overflow: TextOverflow::default(),
+            clear_color: wgpu::Color {r: 0.1, g: 0.1, b: 0.2, a: 1.0},
+            meshes: vec![Mesh::new(context, QUAD, bytemuck::cast_slice(QUAD_INDICES), wgpu::IndexFormat::Uint16, QUAD_INDICES.len() as u32)],
+
+            camera: Camera2D::default(),
+            camera_uniform: None,
+            camera_bind_group_layout,
+
+            sampler_bind_group_layout,
+            samplers,
+            
+            assets_manager,
+
+            quads_instances: QuadsBuckets::new(),
+            retained_batches: Vec::new(),
+            bucket_order: RefCell::new(Vec::new()),
+            white_texture,
+            white,
+            named_textures: HashMap::new(),
+
+            depth_targets: RefCell::new(HashMap::new()),
+            depth_format,
+            depth_clear_value: 1.0,
+            stencil_clear_value: 0,
+            stencil_warned: false,
+            viewport_clear,
+
+            post_process: None,
+
+            surface_size: context.viewport_rect().1,
+            pixels_per_unit: glam::Vec2::ONE,
+            pixel_snapping: false,
+
+            sort_key: None,
+
+            viewport_culling: false,
+            visible_rect: None,
+            culled_instances: 0,
+
+            max_instances_per_bucket: None,
+            instance_limit_warned: false,
+
+            world_target: None,
+            ui_visible_rect: None,
+
+            frame: None,
+            camera_pending: false,
+        })
+    }
+
+    // `None` keeps the clear color of the previous frames
+    pub fn begin(&mut self, clear_color: impl Into<ClearColor>, camera: &Camera2D) {
+
+        if let ClearColor(Some(clear_color)) = clear_color.into() {
+            self.clear_color = clear_color;
+        }
+
+        self.set_camera(camera);
+    }
+
+    // Drops the quads drawn since `begin`, the camera and clear color stay set. Retained batches are
+    // kept, they are cleared through their handle
+    pub fn discard(&mut self) {
+        self.culled_instances = 0;
+        self.quads_instances.clear();
+    }
+
+    // Camera of the last `begin` or `begin_camera`, a unit camera before the first one
+    pub fn camera(&self) -> &Camera2D {
+        &self.camera
+    }
+
+    fn set_camera(&mut self, camera: &Camera2D) {
+        self.camera = camera.clone();
+        self.camera_pending = true;
+        self.camera_uniform = Some(CameraUniform::from_matrix(camera.to_matrix()));
+        self.pixels_per_unit = self.surface_size / camera.viewport_size();
+        self.visible_rect = self.viewport_culling.then(|| camera.visible_rect());
+        self.discard();
+    }
+
+    // Two pass frame: the world is drawn into a fixed size render target with its own camera,
+    // `begin_ui` renders it and starts the UI pass at the surface resolution, `compose` places the
+    // world behind the UI. The world pass is skipped when the target is not loaded
+    pub fn begin_world(&mut self, clear_color: impl Into<ClearColor>, camera: &Camera2D, target: AssetHandle<Texture2D>) {
+        self.begin(clear_color, camera);
+
+        let target_size = {
+            let lock = self.assets_manager.lock().unwrap();
+            lock.try_get_asset(target).map(|texture| glam::vec2(texture.width as f32, texture.height as f32))
+        };
+
+        let Some(target_size) = target_size else {
+            log::error!("World target {target:?} is not loaded, the world is not rendered");
+            self.world_target = None;
+            return;
+        };
+
+        self.pixels_per_unit = target_size / camera.viewport_size();
+        self.world_target = Some(target);
+        self.ui_visible_rect = None;
+    }
+
+    pub fn begin_ui(&mut self, context: &GraphicsContext, camera: &Camera2D) {
+        match self.world_target {
+            Some(target) => self.submit_to_target(context, target, wgpu::LoadOp::Clear(self.clear_color)),
+            None => log::warn!("begin_ui called without begin_world, the world target is not rendered"),
+        }
+
+        self.begin(None, camera);
+        self.ui_visible_rect = Some(camera.visible_rect());
+    }
+
+    // Stretches the world target over the UI camera view, below every UI quad
+    pub fn compose(&mut self) {
+        let (Some(target), Some((min, max))) = (self.world_target, self.ui_visible_rect) else {
+            log::warn!("compose called outside of a begin_world / begin_ui frame");
+            return;
+        };
+
+        let params = DrawParams {
+            z_index: i32::MIN,
+            filter: TextureFilter::Nearest,
+            ..Default::default()
+        };
+
+        self.draw_quad_textured_ex(&Quad::new(min, max - min, 0.0), target, Default::default(), params);
+    }
+
+    // 1x1 white texture to draw solid colors with the texture draw methods, `None` for renderers
+    // created with `color_only`
+    pub fn white_texture(&self) -> Option<AssetHandle<Texture2D>> {
+        self.white_texture
+    }
+
+    pub fn set_clear_color(&mut self, clear_color: wgpu::Color) {
+        self.clear_color = clear_color;
+    }
+
+    // The depth buffer is cleared for every camera, quads are drawn where their depth is less or equal
+    pub fn set_depth_clear_value(&mut self, depth: f32) {
+        self.depth_clear_value = depth.clamp(0.0, 1.0);
+    }
+
+    // The stencil is cleared with the color at the start of the frame and kept between cameras
+    pub fn set_stencil_clear_value(&mut self, stencil: u8) {
+        self.stencil_clear_value = stencil as u32;
+    }
+
+    pub fn clear_color(&self) -> wgpu::Color {
+        self.clear_color
+    }
+
+    // Recreates the size dependent resources owned by the renderer, should be called on `ApplicationEvent::Resized`
+    pub fn resize(&mut self, context: &GraphicsContext, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        self.surface_size = letterbox_rect(width, height, context.maintain_aspect()).1;
+        self.depth_targets.borrow_mut().clear();
+        self.depth_view(context, width, height);
+
+        if let Some(chain) = self.post_process.as_ref().filter(|chain| !chain.is_empty()) {
+            chain.resize(context, width, height);
+        }
+    }
+
+    // Creates a sampler for settings that are not built in, such as anisotropic filtering. The handle
+    // is only valid with this renderer
+    pub fn register_sampler(&mut self, context: &GraphicsContext, settings: SamplerSettings) -> SamplerHandle {
+        self.samplers.push(Self::create_sampler_bind_group(context, &self.sampler_bind_group_layout, settings));
+        SamplerHandle(self.samplers.len() - 1)
+    }
+
+    // Rounds quads translation to whole device pixels, avoids shimmering of pixel art moving at fractional positions
+    pub fn set_pixel_snapping(&mut self, enabled: bool) {
+        self.pixel_snapping = enabled;
+    }
+
+    // Skips quads outside of the camera view, takes effect on the next `begin`
+    pub fn set_viewport_culling(&mut self, enabled: bool) {
+        self.viewport_culling = enabled;
+    }
+
+    // Instance buffers used below a quarter of their size for this many consecutive frames are
+    // shrunk to fit, a larger value avoids reallocations in scenes with bursts of quads. Frames are
+    // counted by `frame_end`, whatever the number of cameras and submits in them.
+    // `None` keeps the buffers at their largest size. Defaults to 300 frames
+    pub fn set_instance_buffer_shrink(&mut self, shrink_after_frames: Option<u32>) {
+        self.quads_instances.set_shrink_after_frames(shrink_after_frames);
+    }
+
+    // Buckets over `max_instances_per_bucket` are drawn in chunks of that many instances, one draw
+    // call each, instead of a single draw of the whole buffer. Unlimited by default
+    pub fn set_instance_limit(&mut self, max_instances_per_bucket: Option<usize>) {
+        self.max_instances_per_bucket = max_instances_per_bucket.map(|max| max.max(1));
+    }
+
+    // Orders the quads of a same z index and texture by ascending key, e.g. `|quad| quad.position().y`
+    // for y-sorting. Custom material instances are not sorted, `None` disables the sorting
+    pub fn set_sort_key(&mut self, sort_key: Option<fn(&Quad) -> f32>) {
+        self.sort_key = sort_key;
+    }
+
+    pub fn stats(&self) -> RenderStats {
+        let mut stats = RenderStats {
+            culled_instances: self.culled_instances,
+            ..Default::default()
+        };
+        let mut current_pipeline = None;
+
+        self.for_each_sorted_bucket(|key, len, _| {
+            let draw_calls = InstancedDraw::draw_calls(self.max_instances_per_bucket, len);
+
+            stats.instances += len;
+            stats.draw_calls += draw_calls;
+
+            if draw_calls > 1 {
+                stats.split_draw_calls += draw_calls;
+            }
+
+            if current_pipeline != Some(key.pipeline_key()) {
+                stats.pipeline_switches += 1;
+                current_pipeline = Some(key.pipeline_key());
+            }
+        });
+
+        stats
+    }
+
+    // Returns the built-in quads of a texture and z index as uploaded to the GPU by the last submit,
+    // helps tracking quads drawn off screen or with broken transforms. Only available in debug builds
+    #[cfg(debug_assertions)]
+    pub fn dump_instances(&self, context: &GraphicsContext, texture_handle: AssetHandle<Texture2D>, z_index: i32) -> Vec<QuadInstanceData> {
+        self.quads_instances.iter()
+            .filter(|(key, _)| key.texture == Some(texture_handle) && key.z_index == z_index && key.material.is_none())
+            .flat_map(|(_, quads)| quads.read_back(context))
+            .collect::<Vec<_>>()
+            .chunks_exact(std::mem::size_of::<QuadInstanceData>())
+            .map(bytemuck::pod_read_unaligned)
+            .collect()
+    }
+
+    // Texture, z index and instance count of the buckets drawn since `begin`, in the order they are
+    // submitted. Retained batches are included, buckets of the same texture and z index are listed
+    // once per material, blend mode or mesh. Solid color buckets have no texture
+    pub fn debug_dump_draw_order(&self) -> Vec<(Option<AssetHandle<Texture2D>>, i32, usize)> {
+        let mut order = vec![];
+        self.for_each_sorted_bucket(|key, len, _| order.push((key.texture, key.z_index, len)));
+
+        order
+    }
+
+    pub fn set_post_process(&mut self, post_process: Option<PostProcessChain>) {
+        self.post_process = post_process;
+    }
+
+    // Retained batches are drawn on every submit until cleared, ordered with the other quads by z index
+    pub fn create_retained_batch(&mut self, texture_handle: AssetHandle<Texture2D>, params: DrawParams) -> RetainedBatchHandle {
+        let params = DrawParams { stencil: self.supported_stencil(params.stencil), ..params };
+        self.retained_batches.push(RetainedBatch::new(texture_handle, params));
+
+        RetainedBatchHandle(self.retained_batches.len() - 1)
+    }
+
+    pub fn retained_batch(&mut self, handle: RetainedBatchHandle) -> &mut RetainedBatch {
+        &mut self.retained_batches[handle.0]
+    }
+
+    pub fn draw_quad(&mut self, quad: &Quad) {
+        self.draw_quad_ex(quad, DrawParams::default());
+    }
+
+    pub fn draw_quad_ex(&mut self, quad: &Quad, params: DrawParams) {
+        self.push_quad(quad, None, Default::default(), params);
+    }
+
+    pub fn draw_quad_sprite(&mut self, quad: &Quad, texture_handle: AssetHandle<Texture2D>) {
+        self.draw_quad_textured(quad, texture_handle, Default::default());
+    }
+
+    // Loads the texture on first use and reuses the handle on the next frames, a texture that
+    // failed to load is reported once and not drawn
+    pub fn draw_sprite_named(&mut self, context: &GraphicsContext, quad: &Quad, path: &str) {
+        let handle = match self.named_textures.get(path) {
+            Some(handle) => *handle,
+            None => {
+                let handle = self.assets_manager.lock().unwrap().load_texture(context, path)
+                    .inspect_err(|err| log::error!("Failed to load texture {path}: {err}"))
+                    .ok();
+
+                self.named_textures.insert(path.to_owned(), handle);
+                handle
+            }
+        };
+
+        if let Some(handle) = handle {
+            self.draw_quad_sprite(quad, handle);
+        }
+    }
+
+    pub fn draw_quad_textured(&mut self, quad: &Quad, texture_handle: AssetHandle<Texture2D>, atlas_coords: Texture2DCoordinates) {
+        self.draw_quad_textured_ex(quad, texture_handle, atlas_coords, DrawParams::default());
+    }
+
+    pub fn draw_quad_textured_ex(
+        &mut self,
+        quad: &Quad,
+        texture_handle: AssetHandle<Texture2D>,
+        atlas_coords: Texture2DCoordinates,
+        params: DrawParams
+    ) {
+        self.push_quad(quad, Some(texture_handle), atlas_coords, params);
+    }
+
+    fn push_quad(&mut self, quad: &Quad, texture: Option<AssetHandle<Texture2D>>, atlas_coords: Texture2DCoordinates, params: DrawParams) {
+        let sort_key = self.sort_key.map(|sort_key| sort_key(quad));
+
+        let quads = self.push_instance(quad_transform(quad, &params), quad.color * params.tint, texture, atlas_coords, params, InstanceShape::default());
+
+        if let (Some(quads), Some(sort_key)) = (quads, sort_key) {
+            quads.push_sort_key(sort_key);
+        }
+    }
+
+    // Draws a copy of the sprite moved by `shadow_offset` one z index below it. The copy is the sprite
+    // multiplied by the shadow color, black gives a flat silhouette, and fades with the sprite alpha
+    pub fn draw_sprite_with_shadow(
+        &mut self,
+        quad: &Quad,
+        texture_handle: AssetHandle<Texture2D>,
+        atlas_coords: Texture2DCoordinates,
+        params: DrawParams,
+        shadow_offset: glam::Vec2,
+        shadow_color: glam::Vec4
+    ) {
+        let mut shadow = Quad::new(quad.position() + shadow_offset, quad.size(), quad.rotation());
+        shadow.color = shadow_color * glam::vec4(1.0, 1.0, 1.0, quad.color.w * params.tint.w);
+
+        let shadow_params = DrawParams {
+            tint: glam::Vec4::ONE,
+            z_index: params.z_index.saturating_sub(1),
+            opaque: false,
+            pivot: params.pivot.map(|pivot| pivot + shadow_offset),
+            ..params
+        };
+
+        self.draw_quad_textured_ex(&shadow, texture_handle, atlas_coords, shadow_params);
+        self.draw_quad_textured_ex(quad, texture_handle, atlas_coords, params);
+    }
+
+    // Multiplies the sprite alpha by the red channel of the mask, sampled with the same coordinates
+    pub fn draw_quad_masked(
+        &mut self,
+        quad: &Quad,
+        texture_handle: AssetHandle<Texture2D>,
+        mask_handle: AssetHandle<Texture2D>,
+        atlas_coords: Texture2DCoordinates
+    ) {
+        let sort_key = self.sort_key.map(|sort_key| sort_key(quad));
+
+        let quads = self.push_instance(quad.get_transform(), quad.color, Some(texture_handle), atlas_coords, DrawParams::default(), InstanceShape { mask: Some(mask_handle), ..Default::default() });
+
+        if let (Some(quads), Some(sort_key)) = (quads, sort_key) {
+            quads.push_sort_key(sort_key);
+        }
+    }
+
+    // Corner colors in top left, top right, bottom right, bottom left order, interpolated across the quad
+    pub fn draw_quad_gradient(&mut self, quad: &Quad, colors: [glam::Vec4; 4]) {
+        self.draw_quad_gradient_ex(quad, colors, DrawParams::default());
+    }
+
+    // Gradients are alpha blended, the `opaque`, `blend` and `stencil` draw params do not apply
+    pub fn draw_quad_gradient_ex(&mut self, quad: &Quad, colors: [glam::Vec4; 4], params: DrawParams) {
+        let Some(model) = self.place_instance(quad_transform(quad, &params), None, &params, MeshId::QUAD) else {
+            return;
+        };
+
+        let instance = GradientInstanceData {
+            model,
+            colors: colors.map(|color| (color * params.tint).into()),
+        };
+
+        self.push_material_instance(self.gradient_material, None, instance, params);
+    }
+
+    // The quad with its corners rounded by `corner_radius` world units, clamped to half the smallest side
+    pub fn draw_rounded_rect(&mut self, quad: &Quad, corner_radius: f32) {
+        self.draw_rounded_rect_ex(quad, corner_radius, DrawParams::default());
+    }
+
+    // Shapes are alpha blended, the `opaque`, `blend` and `stencil` draw params do not apply
+    pub fn draw_rounded_rect_ex(&mut self, quad: &Quad, corner_radius: f32, params: DrawParams) {
+        self.draw_sdf(quad, corner_radius, params);
+    }
+
+    pub fn draw_circle(&mut self, center: glam::Vec2, radius: f32, color: glam::Vec4) {
+        self.draw_circle_ex(center, radius, color, DrawParams::default());
+    }
+
+    pub fn draw_circle_ex(&mut self, center: glam::Vec2, radius: f32, color: glam::Vec4, params: DrawParams) {
+        let mut quad = Quad::new(center - glam::Vec2::splat(radius), glam::Vec2::splat(radius * 2.0), 0.0);
+        quad.color = color;
+
+        self.draw_sdf(&quad, radius, params);
+    }
+
+    // Width in pixels of the anti-aliased edge of circles, rounded rects and SDF text, 0 gives hard
+    // edges. The shapes are smoothed in screen space so the edge stays the same at every zoom level
+    pub fn set_sdf_edge_softness(&mut self, pixels: f32) {
+        self.sdf_edge_softness = clamp_edge_softness(pixels);
+    }
+
+    fn draw_sdf(&mut self, quad: &Quad, corner_radius: f32, params: DrawParams) {
+        let Some(model) = self.place_instance(quad_transform(quad, &params), None, &params, MeshId::QUAD) else {
+            return;
+        };
+
+        let size = quad.size();
+
+        let instance = SdfInstanceData {
+            model,
+            color: (quad.color * params.tint).into(),
+            shape: [size.x, size.y, corner_radius, self.sdf_edge_softness],
+        };
+
+        self.push_material_instance(self.sdf_material, None, instance, params);
+    }
+
+    pub fn set_font(&mut self, font: Option<BitmapFont>) {
+        self.font = font;
+    }
+
+    pub fn font(&self) -> Option<&BitmapFont> {
+        self.font.as_ref()
+    }
+
+    pub fn set_text_overflow(&mut self, overflow: TextOverflow) {
+        self.text_overflow = overflow;
+    }
+
+    // Draws the text with the current font from its top left corner, lines are split on line breaks only
+    pub fn draw_text(&mut self, text: &str, position: glam::Vec2, scale: f32, color: glam::Vec4) {
+        self.draw_text_ex(text, position, scale, color, DrawParams::default());
+    }
+
+    // The params apply to every glyph, SDF fonts only use the z index
+    pub fn draw_text_ex(&mut self, text: &str, position: glam::Vec2, scale: f32, color: glam::Vec4, params: DrawParams) {
+        let lines = text.lines().collect::<Vec<_>>();
+        self.draw_text_lines(&lines, position, scale, color, None, params);
+    }
+
+    // Wraps the text on word boundaries to the width of `bounds` (x, y, width, height) and returns the
+    // height of all the lines, including the ones clipped by `set_text_overflow`
+    pub fn draw_text_wrapped(&mut self, text: &str, bounds: glam::Vec4, scale: f32, color: glam::Vec4) -> f32 {
+        let Some(font) = &self.font else {
+            log::warn!("No font is set, text is not drawn");
+            return 0.0;
+        };
+
+        let advance = font.glyph_size().x * scale;
+        let max_columns = if advance > 0.0 { (bounds.z / advance).floor() as usize } else { usize::MAX };
+
+        let lines = text::wrap_lines(text, max_columns);
+        let max_height = (self.text_overflow == TextOverflow::Clip).then_some(bounds.w);
+
+        self.draw_text_lines(&lines, glam::vec2(bounds.x, bounds.y), scale, color, max_height, DrawParams::default())
+    }
+
+    fn draw_text_lines(
+        &mut self,
+        lines: &[impl AsRef<str>],
+        position: glam::Vec2,
+        scale: f32,
+        color: glam::Vec4,
+        max_height: Option<f32>,
+        params: DrawParams
+    ) -> f32 {
+        let Some(font) = &self.font else {
+            log::warn!("No font is set, text is not drawn");
+            return 0.0;
+        };
+
+        let glyph_size = font.glyph_size() * scale;
+        let line_height = font.line_height() * scale;
+        let texture = font.texture();
+        let sdf = font.is_sdf();
+
+        let mut glyphs = vec![];
+
+        for (row, line) in lines.iter().enumerate() {
+            let top = row as f32 * line_height;
+
+            if max_height.is_some_and(|max_height| top + glyph_size.y > max_height) {
+                break;
+            }
+
+            for (column, c) in line.as_ref().chars().enumerate() {
+                let Some(coords) = font.glyph_index(c).and_then(|index| font.glyphs().get_coords_by_index(index)) else {
+                    continue;
+                };
+
+                let mut quad = Quad::new(position + glam::vec2(column as f32 * glyph_size.x, top), glyph_size, 0.0);
+                quad.color = color;
+
+                glyphs.push((quad, coords));
+            }
+        }
+
+        for (quad, coords) in &glyphs {
+            if sdf {
+                self.draw_sdf_glyph(quad, texture, *coords, params.z_index);
+            }
+            else {
+                self.draw_quad_textured_ex(quad, texture, *coords, params);
+            }
+        }
+
+        lines.len() as f32 * line_height
+    }
+
+    // The distance field is interpolated between texels, nearest sampling would give blocky edges
+    fn draw_sdf_glyph(&mut self, quad: &Quad, texture: AssetHandle<Texture2D>, coords: Texture2DCoordinates, z_index: i32) {
+        let mut model = quad.get_transform();
+        model.w_axis.z = z_index_to_depth(z_index);
+
+        let instance = SdfTextInstanceData {
+            model,
+            color: quad.color.into(),
+            tex_coords_size: coords.size,
+            tex_coords_offset: coords.offset,
+            edge_softness: self.sdf_edge_softness,
+            _padding: [0.0; 3],
+        };
+
+        let params = DrawParams { z_index, sampler: Some(SamplerHandle::LINEAR_CLAMP), ..Default::default() };
+        self.draw_instance(self.sdf_text_material, texture, instance, params);
+    }
+
+    pub fn draw_quad_bordered(&mut self, quad: &Quad, fill_color: glam::Vec4, border_color: glam::Vec4, border_thickness: f32) {
+        self.draw_quad_bordered_ex(quad, fill_color, border_color, border_thickness, BorderPlacement::Inside);
+    }
+
+    // The fill covers the quad and the border is drawn over it, both in the quad bucket order
+    pub fn draw_quad_bordered_ex(
+        &mut self,
+        quad: &Quad,
+        fill_color: glam::Vec4,
+        border_color: glam::Vec4,
+        border_thickness: f32,
+        placement: BorderPlacement
+    ) {
+        let mut fill = Quad::new(quad.position(), quad.size(), quad.rotation());
+        fill.color = fill_color;
+
+        self.draw_quad(&fill);
+
+        match placement {
+            BorderPlacement::Inside => self.draw_quad_outline(quad, border_thickness, border_color),
+            BorderPlacement::Outside => {
+                let grow = glam::Vec2::splat(border_thickness);
+                let outer = Quad::new(quad.position() - grow, quad.size() + grow * 2.0, quad.rotation());
+
+                self.draw_quad_outline(&outer, border_thickness, border_color);
+            }
+        }
+    }
+
+    // The outline is drawn inside the quad bounds and follows the quad rotation
+    pub fn draw_quad_outline(&mut self, quad: &Quad, thickness: f32, color: glam::Vec4) {
+        let size = quad.size();
+        let thickness = thickness.min(size.x * 0.5).min(size.y * 0.5);
+
+        let center = size * 0.5;
+        let rotation = glam::Quat::from_rotation_z(quad.rotation().to_radians());
+        let parent = glam::Mat4::from_rotation_translation(rotation, (quad.position() + center).extend(0.0));
+
+        let sort_key = self.sort_key.map(|sort_key| sort_key(quad));
+
+        let sides = [
+            (glam::vec2(0.0, 0.0), glam::vec2(size.x, thickness)),
+            (glam::vec2(0.0, size.y - thickness), glam::vec2(size.x, thickness)),
+            (glam::vec2(0.0, thickness), glam::vec2(thickness, size.y - 2.0 * thickness)),
+            (glam::vec2(size.x - thickness, thickness), glam::vec2(thickness, size.y - 2.0 * thickness)),
+        ];
+
+        for (offset, side_size) in sides {
+            let model = parent
+                * glam::Mat4::from_translation((offset - center).extend(0.0))
+                * glam::Mat4::from_scale(side_size.extend(1.0));
+
+            let quads = self.push_instance(model, color, None, Default::default(), DrawParams::default(), InstanceShape::default());
+
+            if let (Some(quads), Some(sort_key)) = (quads, sort_key) {
+                quads.push_sort_key(sort_key);
+            }
+        }
+    }
+
+    // Indexed triangles drawn like quads: one instance per quad transform with the quad shaders,
+    // textures and draw parameters. Positions are in the quad unit square space
+    pub fn register_mesh(&mut self, context: &GraphicsContext, vertices: &[Vertex], indices: &[u32]) -> MeshId {
+        if !indices.len().is_multiple_of(3) || indices.iter().any(|&index| index as usize >= vertices.len()) {
+            log::warn!("Mesh indices do not form triangles of its {} vertices", vertices.len());
+        }
+
+        self.meshes.push(Mesh::new(context, vertices, bytemuck::cast_slice(indices), wgpu::IndexFormat::Uint32, indices.len() as u32));
+
+        MeshId(self.meshes.len() - 1)
+    }
+
+    pub fn draw_mesh_instanced(&mut self, mesh: MeshId, texture_handle: AssetHandle<Texture2D>, instances: &[Quad]) {
+        self.draw_mesh_instanced_ex(mesh, texture_handle, instances, DrawParams::default());
+    }
+
+    pub fn draw_mesh_instanced_ex(&mut self, mesh: MeshId, texture_handle: AssetHandle<Texture2D>, instances: &[Quad], params: DrawParams) {
+        if mesh.0 >= self.meshes.len() {
+            log::warn!("Mesh {mesh:?} is not registered on this renderer");
+            return;
+        }
+
+        for quad in instances {
+            let sort_key = self.sort_key.map(|sort_key| sort_key(quad));
+            let shape = InstanceShape { mesh, mask: None };
+
+            let quads = self.push_instance(quad.get_transform(), quad.color * params.tint, Some(texture_handle), Default::default(), params, shape);
+
+            if let (Some(quads), Some(sort_key)) = (quads, sort_key) {
+                quads.push_sort_key(sort_key);
+            }
+        }
+    }
+
+    // The layer origin follows the camera by `1 - scroll_factor`, tiles are drawn to cover the visible rect
+    pub fn draw_parallax(&mut self, layer: &ParallaxLayer, camera: &Camera2D) {
+        if layer.tile_size.x <= 0.0 || layer.tile_size.y <= 0.0 {
+            return;
+        }
+
+        let (visible_min, visible_max) = camera.visible_rect();
+        let origin = visible_min * (glam::Vec2::ONE - layer.scroll_factor) + layer.offset;
+
+        let first = ((visible_min - origin) / layer.tile_size).floor();
+        let last = ((visible_max - origin) / layer.tile_size).ceil();
+
+        let params = DrawParams {
+            z_index: layer.z_index,
+            ..Default::default()
+        };
+
+        for y in first.y as i32..last.y as i32 {
+            for x in first.x as i32..last.x as i32 {
+                let position = origin + glam::vec2(x as f32, y as f32) * layer.tile_size;
+                let quad = Quad::new(position, layer.tile_size, 0.0);
+
+                self.draw_quad_textured_ex(&quad, layer.texture, Default::default(), params);
+            }
+        }
+    }
+
+    // The shader gets the same bind groups and quad vertices as `shader_quad.wgsl` and must define
+    // `vs_main` and `fs_main`, the instances are blended and drawn with the transparent quads
+    pub fn create_material<T: InstanceData>(&mut self, context: &GraphicsContext, label: &str, shader_source: &str) -> InstanceMaterial<T> {
+        let shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(shader_source.into()),
+        });
+
+        let pipeline = Self::create_render_pipeline(
+            context,
+            &self.render_pipeline_layout,
+            &shader,
+            label,
+            T::desc(),
+            "fs_main",
+            PipelineState { depth_format: self.depth_format, blend: Some(BlendMode::Alpha), stencil: StencilTest::Disabled }
+        );
+
+        self.materials.push(pipeline);
+
+        InstanceMaterial {
+            index: self.materials.len() - 1,
+            _marker: PhantomData,
+        }
+    }
+
+    // Only the `z_index` and sampling draw params apply, the instance is passed to the shader as is
+    pub fn draw_instance<T: InstanceData>(
+        &mut self,
+        material: InstanceMaterial<T>,
+        texture_handle: AssetHandle<Texture2D>,
+        instance: T,
+        params: DrawParams
+    ) {
+        self.push_material_instance(material, Some(texture_handle), instance, params);
+    }
+
+    fn push_material_instance<T: InstanceData>(&mut self, material: InstanceMaterial<T>, texture: Option<AssetHandle<Texture2D>>, instance: T, params: DrawParams) {
+        let key = BucketKey {
+            texture,
+            z_index: params.z_index,
+            sampler: params.sampler_handle(),
+            opaque: false,
+            blend: BlendMode::Alpha,
+            stencil: StencilMode::Disabled,
+            material: Some(material.index),
+            mask: None,
+            mesh: MeshId::QUAD,
+        };
+
+        self.bucket(key, std::mem::size_of::<T>()).push(instance);
+    }
+
+    fn bucket(&mut self, key: BucketKey, stride: usize) -> &mut QuadsInstanceDataBuffer {
+        let instances = self.quads_instances.get_or_insert(key, Self::MAX_QUAD, stride);
+
+        if !self.instance_limit_warned && self.max_instances_per_bucket.is_some_and(|max| instances.len() == max) {
+            log::warn!("Instance limit of {} reached for texture {:?} z index {}, the bucket is drawn in chunks", instances.len(), key.texture, key.z_index);
+            self.instance_limit_warned = true;
+        }
+
+        instances
+    }
+
+    fn push_instance(
+        &mut self,
+        model: glam::Mat4,
+        color: glam::Vec4,
+        texture: Option<AssetHandle<Texture2D>>,
+        atlas_coords: Texture2DCoordinates,
+        params: DrawParams,
+        shape: InstanceShape
+    ) -> Option<&mut QuadsInstanceDataBuffer> {
+        let model = self.place_instance(model, texture, &params, shape.mesh)?;
+
+        let key = BucketKey {
+            texture,
+            z_index: params.z_index,
+            sampler: params.sampler_handle(),
+            opaque: params.opaque && shape.mask.is_none() && params.blend == BlendMode::Alpha,
+            blend: params.blend,
+            stencil: self.supported_stencil(params.stencil),
+            material: None,
+            mask: shape.mask,
+            mesh: shape.mesh,
+        };
+
+        let quads = self.bucket(key, std::mem::size_of::<QuadInstanceData>());
+
+        quads.push(QuadInstanceData::new(model, color, atlas_coords, params.flip));
+
+        Some(quads)
+    }
+
+    // Skips the degenerate and culled instances, then snaps the model to the pixel grid and sets its
+    // depth from the z index. Shared by the quads and the built-in materials
+    fn place_instance(&mut self, mut model: glam::Mat4, texture: Option<AssetHandle<Texture2D>>, params: &DrawParams, mesh: MeshId) -> Option<glam::Mat4> {
+        if cfg!(debug_assertions) && is_degenerate_transform(&model) {
+            log::warn!(
+                "Skipping quad with a non finite or zero area transform, texture {texture:?} z index {} translation {}",
+                params.z_index,
+                model.w_axis.truncate()
+            );
+            return None;
+        }
+
+        if let Some((visible_min, visible_max)) = self.visible_rect {
+            let (quad_min, quad_max) = transformed_aabb(&model, self.meshes[mesh.0].bounds);
+
+            if quad_max.cmplt(visible_min).any() || quad_min.cmpgt(visible_max).any() {
+                self.culled_instances += 1;
+                return None;
+            }
+        }
+
+        if self.pixel_snapping {
+            let translation = snap_to_pixel_grid(model.w_axis.truncate().truncate(), self.pixels_per_unit);
+            model.w_axis.x = translation.x;
+            model.w_axis.y = translation.y;
+        }
+
+        model.w_axis.z = z_index_to_depth(params.z_index);
+
+        Some(model)
+    }
+
+    // Acquires the surface texture, the quads queued for each camera are rendered into it by
+    // `begin_camera` and `frame_end`. The first camera pass clears the frame
+    pub fn frame_begin(&mut self, context: &GraphicsContext) -> Result<(), wgpu::SurfaceError> {
+        let output = context.surface.get_current_texture()?;
+        let view = output.texture.create_view(&Default::default());
+
+        self.frame = Some(Frame {
+            output,
+            view,
+            cleared: false,
+        });
+
+        Ok(())
+    }
+
+    // Renders the quads queued with the previous camera then starts queuing quads for this one
+    pub fn begin_camera(&mut self, context: &GraphicsContext, camera: &Camera2D) {
+        self.flush_camera(context);
+        self.set_camera(camera);
+    }
+
+    // Renders the last camera quads, applies the post process chain and presents the frame
+    pub fn frame_end(&mut self, context: &GraphicsContext) {
+        self.flush_camera(context);
+
+        let Some(frame) = self.frame.take() else {
+            log::warn!("frame_end called without frame_begin");
+            return;
+        };
+
+        if let Some(chain) = self.post_process.as_ref().filter(|chain| !chain.is_empty()) {
+            let mut encoder = context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Renderer2D post process commands encoder"),
+            });
+
+            chain.apply(&mut encoder, &frame.view);
+            context.queue.submit(std::iter::once(encoder.finish()));
+        }
+
+        context.render_overlays(&frame.output.texture);
+        context.capture_pending_screenshot(&frame.output.texture);
+        frame.output.present();
+
+        self.quads_instances.end_frame();
+    }
+
+    pub fn submit(&mut self, context: &GraphicsContext) -> Result<(), wgpu::SurfaceError> {
+        self.frame_begin(context)?;
+        self.frame_end(context);
+        Ok(())
+    }
+
+    fn flush_camera(&mut self, context: &GraphicsContext) {
+        let Some(frame) = self.frame.as_mut() else {
+            return;
+        };
+
+        if frame.cleared && !self.camera_pending {
+            return;
+        }
+
+        let load = if frame.cleared {
+            wgpu::LoadOp::Load
+        }
+        else {
+            wgpu::LoadOp::Clear(self.clear_color)
+        };
+
+        frame.cleared = true;
+        self.camera_pending = false;
+
+        let frame = self.frame.as_ref().unwrap();
+
+        let mut encoder = context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Renderer2D commands encoder"),
+        });
+
+        let viewport = context.maintain_aspect().map(|_| context.viewport_rect());
+
+        match self.post_process.as_ref().filter(|chain| !chain.is_empty()) {
+            Some(chain) => {
+                let scene_view = chain.scene_view(context);
+                self.start_render_pass(context, &mut encoder, &scene_view, load, viewport, self.camera_uniform);
+            }
+            None => {
+                self.start_render_pass(context, &mut encoder, &frame.view, load, viewport, self.camera_uniform);
+            }
+        }
+
+        context.queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    // Render graph node drawing the queued quads into `output`, cleared with the renderer clear color.
+    // The post process chain is not applied, it can be replaced by passes reading `output`
+    pub fn scene_pass(&self, output: impl Into<String>) -> RenderGraphPass<'_> {
+        RenderGraphPass::new("Renderer2D scene", output, move |context, encoder, resources| {
+            let viewport = (resources.is_surface_output() && context.maintain_aspect().is_some()).then(|| context.viewport_rect());
+
+            self.start_render_pass(context, encoder, resources.output(), wgpu::LoadOp::Clear(self.clear_color), viewport, self.camera_uniform);
+        })
+    }
+
+    // Renders the queued quads into a texture created with `Texture2D::new_render_target`.
+    // The target uses its own load operation, the renderer clear color only applies to the surface
+    pub fn submit_to_target(&self, context: &GraphicsContext, target: AssetHandle<Texture2D>, load: wgpu::LoadOp<wgpu::Color>) {
+        self.render_to_target(context, target, load, None);
+    }
+
+    // Renders the quads queued since `begin` into `target` as seen by another camera, the instances
+    // are not rebuilt and stay queued until the next `begin` or `begin_camera`, so the same batch can
+    // be drawn from several views in one frame. Quads drawn after this call only appear in the later
+    // submits. Culling and pixel snapping follow the `begin` camera, disable culling when the views
+    // look at different areas
+    pub fn submit_view(&self, context: &GraphicsContext, camera: &Camera2D, target: AssetHandle<Texture2D>, load: wgpu::LoadOp<wgpu::Color>) {
+        self.render_to_target(context, target, load, Some(camera));
+    }
+
+    fn render_to_target(&self, context: &GraphicsContext, target: AssetHandle<Texture2D>, load: wgpu::LoadOp<wgpu::Color>, view_camera: Option<&Camera2D>) {
+        let Some(view) = self.assets_manager.lock().unwrap().try_get_asset(target).map(|texture| texture.view.clone()) else {
+            log::error!("Render target {target:?} is not loaded, the submit is skipped");
+            return;
+        };
+        let camera = pass_camera_uniform(self.camera_uniform, view_camera);
+
+        let mut encoder = context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Renderer2D render target commands encoder"),
+        });
+
+        self.start_render_pass(context, &mut encoder, &view, load, None, camera);
+
+        context.queue.submit(std::iter::once(encoder.finish()));
+    }
 
-    fn start_render_pass(&self, context: &GraphicsContext, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+
+    fn start_render_pass(
+        &self,
+        context: &GraphicsContext,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+        // Letterboxed (position, size) area of the view to draw to
+        viewport: Option<(glam::Vec2, glam::Vec2)>,
+        camera: Option<CameraUniform>
+    ) {
+        let depth_view = self.depth_view(context, view.texture().width(), view.texture().height());
+
+        let stencil_load = match load {
+            wgpu::LoadOp::Clear(_) => wgpu::LoadOp::Clear(self.stencil_clear_value),
+            _ => wgpu::LoadOp::Load,
+        };
+
+        let (load, viewport_clear_color) = match (viewport, load) {
+            (Some(_), wgpu::LoadOp::Clear(color)) => (wgpu::LoadOp::Clear(wgpu::Color::BLACK), Some(color)),
+            _ => (load, None),
+        };
 
         let mut render_pass= encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Renderer2D color render pass"),
             color_attachments: &[
                 Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(self.clear_color),
+                        load,
                         store: wgpu::StoreOp::Store,    
                     },
                     depth_slice: None,
                 })
             ],
-            depth_stencil_attachment: None,
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.depth_clear_value),
+                    store: wgpu::StoreOp::Discard,
+                }),
+                stencil_ops: self.depth_format.has_stencil_aspect().then_some(wgpu::Operations {
+                    load: stencil_load,
+                    store: wgpu::StoreOp::Store,
+                }),
+            }),
             timestamp_writes: None,
             occlusion_query_set: None,
         });
 
+        if let Some((position, size)) = viewport {
+            render_pass.set_viewport(position.x, position.y, size.x, size.y, 0.0, 1.0);
+        }
+
+        if let Some(color) = viewport_clear_color {
+            self.viewport_clear.draw(context, &mut render_pass, color);
+        }
 
-        render_pass.set_pipeline(&self.render_pipeline);
+        // A frame ended without any camera is only cleared
+        let Some(camera) = camera else {
+            return;
+        };
 
-        render_pass.set_bind_group(0, &self.create_camera_bind_group(context), &[]);
-        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.set_bind_group(0, &self.create_camera_bind_group(context, camera), &[]);
 
         self.render_quads(context, &mut render_pass);
 
     }
 
     fn render_quads(&self, context: &GraphicsContext, render_pass: &mut wgpu::RenderPass) {
-        let lock = self.assets_manager.lock().unwrap(); 
-
-        for (handle, quads) in &self.quads_instances {
+        let mut lock = self.assets_manager.lock().unwrap(); 
+
+        // Textures evicted by the assets manager budget are brought back before being drawn
+        let used_textures = self.quads_instances.iter()
+            .filter(|(_, quads)| quads.len() > 0)
+            .map(|(key, _)| key)
+            .chain(self.retained_batches.iter().filter(|batch| !batch.is_empty()).map(|batch| &batch.key))
+            .flat_map(|key| [key.texture, key.mask])
+            .flatten()
+            .collect::<Vec<_>>();
+
+        for texture in used_textures {
+            if !lock.is_loaded(texture) && !lock.is_pending(texture) && let Err(err) = lock.reload_texture(context, texture) {
+                log::error!("Failed to reload evicted texture {texture:?}: {err}");
+            }
+        }
 
-            let texture= lock.get_asset(*handle);
+        let mut current_pipeline = None;
+        let mut current_stencil_reference = None;
+        let mut current_mesh = None;
+
+        self.for_each_sorted_bucket(|key, _, quads| {
+            let pipeline_key = key.pipeline_key();
+            let stencil = key.stencil.test();
+
+            if current_pipeline != Some(pipeline_key) {
+                let pipeline = match key.material {
+                    Some(index) => &self.materials[index],
+                    None if key.mask.is_some() => &self.masked_render_pipelines[&(key.blend, stencil)],
+                    None if key.opaque => &self.opaque_render_pipelines[&stencil],
+                    None => &self.render_pipelines[&(key.blend, stencil)],
+                };
+
+                render_pass.set_pipeline(pipeline);
+                current_pipeline = Some(pipeline_key);
+            }
+
+            if stencil != StencilTest::Disabled && current_stencil_reference != Some(key.stencil.reference()) {
+                render_pass.set_stencil_reference(key.stencil.reference());
+                current_stencil_reference = Some(key.stencil.reference());
+            }
+
+            let texture = match key.texture {
+                Some(texture) => lock.try_get_asset(texture),
+                None => Some(&self.white),
+            };
+
+            let Some(texture) = texture else {
+                return;
+            };
+
+            if let Some(mask) = key.mask {
+                let Some(mask) = lock.try_get_asset(mask) else {
+                    return;
+                };
+
+                render_pass.set_bind_group(3, &mask.bind_group, &[]);
+            }
+
+            let sampler_bind_group = &self.samplers[key.sampler.0];
+            let mesh = &self.meshes[key.mesh.0];
+
+            if current_mesh != Some(key.mesh) {
+                render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(mesh.index_buffer.slice(..), mesh.index_format);
+                current_mesh = Some(key.mesh);
+            }
 
             render_pass.set_bind_group(1, &texture.bind_group, &[]);
+            render_pass.set_bind_group(2, sampler_bind_group, &[]);
+
+            let draw = InstancedDraw {
+                index_count: mesh.index_count,
+                max_instances_per_bucket: self.max_instances_per_bucket,
+            };
+
+            quads.submit_to_render_pass(context, render_pass, draw);
+        });
+    }
+
+    // Opaque buckets go first front-to-back so the depth test rejects hidden fragments, the
+    // blended ones follow back-to-front. Buckets of the same z index are kept together by pipeline
+    // Empty buckets are skipped. The frames drawing the same buckets as the previous one reuse
+    // the cached order without sorting nor allocating
+    fn for_each_sorted_bucket<'s>(&'s self, mut f: impl FnMut(&'s BucketKey, usize, &'s dyn InstanceSource)) {
+        let bucket_count = self.quads_instances.len() + self.retained_batches.len();
+
+        if self.bucket_order.borrow().len() != bucket_count {
+            let mut order = self.bucket_order.borrow_mut();
+
+            order.clear();
+            order.extend((0..self.quads_instances.len()).map(BucketSlot::Quads));
+            order.extend((0..self.retained_batches.len()).map(BucketSlot::Retained));
+
+            order.sort_by(|a, b| {
+                let (a, b) = (self.bucket_at(*a).0, self.bucket_at(*b).0);
+
+                b.opaque.cmp(&a.opaque).then_with(|| if a.opaque {
+                    b.z_index.cmp(&a.z_index)
+                }
+                else {
+                    a.z_index.cmp(&b.z_index)
+                })
+                .then_with(|| a.pipeline_key().cmp(&b.pipeline_key()))
+                .then_with(|| a.mesh.cmp(&b.mesh))
+            });
+        }
+
+        for slot in self.bucket_order.borrow().iter() {
+            let (key, len, source) = self.bucket_at(*slot);
+
+            if len > 0 {
+                f(key, len, source);
+            }
+        }
+    }
+
+    fn bucket_at(&self, slot: BucketSlot) -> (&BucketKey, usize, &dyn InstanceSource) {
+        match slot {
+            BucketSlot::Quads(index) => {
+                let (key, quads) = self.quads_instances.get(index);
+                (key, quads.len(), quads)
+            }
+            BucketSlot::Retained(index) => {
+                let batch = &self.retained_batches[index];
+                (&batch.key, batch.len(), batch)
+            }
+        }
+    }
 
-            quads.submit_to_render_pass(context, render_pass);
+    fn supported_stencil(&mut self, stencil: StencilMode) -> StencilMode {
+        if stencil == StencilMode::Disabled || self.depth_format.has_stencil_aspect() {
+            return stencil;
         }
+
+        if !self.stencil_warned {
+            log::warn!("Stencil modes need a renderer created with Renderer2D::with_stencil, drawing without stencil");
+            self.stencil_warned = true;
+        }
+
+        StencilMode::Disabled
+    }
+
+    fn depth_view(&self, context: &GraphicsContext, width: u32, height: u32) -> wgpu::TextureView {
+        let size = (width, height);
+
+        if let Some(view) = self.depth_targets.borrow().get(&size) {
+            return view.clone();
+        }
+
+        log::info!("Creating depth target {}x{}", width, height);
+
+        let texture = context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Renderer2D depth target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.depth_format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&Default::default());
+        self.depth_targets.borrow_mut().insert(size, view.clone());
+
+        view
+    }
+
+    fn create_render_pipeline(
+        context: &GraphicsContext,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        label: &str,
+        instance_layout: wgpu::VertexBufferLayout,
+        fragment_entry_point: &str,
+        state: PipelineState
+    ) -> wgpu::RenderPipeline {
+        context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[
+                    Vertex::desc(),
+                    instance_layout
+                ],
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: state.depth_format,
+                depth_write_enabled: state.blend.is_none(),
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: state.stencil.stencil_state(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some(fragment_entry_point),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: context.config.format,
+                    blend: state.blend.map(BlendMode::blend_state),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            })
+        })
     }
-    fn create_camera_bind_group(&self, context: &GraphicsContext) -> wgpu::BindGroup {
 
-        context.queue.write_buffer(
-            &self.camera_buffer, 0, 
-            bytemuck::cast_slice(&[self.camera_uniform.unwrap()]));
+    // Every pass gets its own camera buffer, passes recorded in one encoder can use different cameras
+    fn create_camera_bind_group(&self, context: &GraphicsContext, camera: CameraUniform) -> wgpu::BindGroup {
+        let camera_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Renderer2D camera buffer"),
+            contents: bytemuck::cast_slice(&[camera]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
 
         context.device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Renderer2D camera bind group"),
@@ -348,26 +2517,166 @@ impl Renderer2D {
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: self.camera_buffer.as_entire_binding(),
+                    resource: camera_buffer.as_entire_binding(),
                 }
             ],
         })
     }
 
-    fn create_vertex_buffer(context: &GraphicsContext) -> wgpu::Buffer {
-        context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Renderer2D vertext buffer"),
-            contents: bytemuck::cast_slice(QUAD),
-            usage: wgpu::BufferUsages::VERTEX
+    fn create_sampler_bind_group(context: &GraphicsContext, layout: &wgpu::BindGroupLayout, settings: SamplerSettings) -> wgpu::BindGroup {
+        let filter = match settings.filter {
+            TextureFilter::Linear => wgpu::FilterMode::Linear,
+            TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+        };
+
+        let clamp_to_border = context.device.features().contains(wgpu::Features::ADDRESS_MODE_CLAMP_TO_BORDER);
+
+        let (address_mode, border_color) = match settings.wrap {
+            TextureWrap::ClampToEdge => (wgpu::AddressMode::ClampToEdge, None),
+            TextureWrap::Repeat => (wgpu::AddressMode::Repeat, None),
+            TextureWrap::MirrorRepeat => (wgpu::AddressMode::MirrorRepeat, None),
+            TextureWrap::ClampToBorder(color) if clamp_to_border => (wgpu::AddressMode::ClampToBorder, Some(color.into())),
+            TextureWrap::ClampToBorder(_) => (wgpu::AddressMode::ClampToEdge, None),
+        };
+
+        // Anisotropic filtering is only valid with linear filters
+        let anisotropy = if settings.filter == TextureFilter::Linear { settings.anisotropy.clamp(1, 16) } else { 1 };
+
+        if anisotropy != settings.anisotropy.max(1) {
+            log::warn!("Sampler anisotropy {} is not supported with {:?} filtering, using {anisotropy}", settings.anisotropy, settings.filter);
+        }
+
+        let sampler = context.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Renderer2D sampler"),
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: if anisotropy > 1 { wgpu::FilterMode::Linear } else { wgpu::FilterMode::Nearest },
+            border_color,
+            anisotropy_clamp: anisotropy,
+            ..Default::default()
+        });
+
+        context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Renderer2D sampler bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                }
+            ],
         })
     }
 
-    fn create_index_buffer(context: &GraphicsContext) -> wgpu::Buffer {
-        context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Renderer2D index buffer"),
-            contents: bytemuck::cast_slice(QUAD_INDICES),
-            usage: wgpu::BufferUsages::INDEX
-        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pass_camera_uniform_prefers_view_camera() {
+        let active = CameraUniform::from_matrix(Camera2D::new(800.0, 600.0).to_matrix());
+
+        let mut view_camera = Camera2D::new(200.0, 100.0);
+        view_camera.add_shake(1.0);
+        view_camera.update(0.0);
+
+        assert_eq!(pass_camera_uniform(None, None), None);
+        assert_eq!(pass_camera_uniform(Some(active), None), Some(active));
+        assert_eq!(pass_camera_uniform(None, Some(&view_camera)), Some(CameraUniform::from_matrix(view_camera.to_matrix())));
+
+        // The view matrix includes the shake offset of the view camera
+        let view_uniform = pass_camera_uniform(Some(active), Some(&view_camera));
+        assert_eq!(view_uniform, Some(CameraUniform::from_matrix(view_camera.to_matrix())));
+        assert_ne!(view_uniform, Some(active));
+    }
+
+    #[test]
+    fn buckets_over_the_limit_are_drawn_in_chunks() {
+        assert_eq!(InstancedDraw::draw_calls(None, 250), 1);
+        assert_eq!(InstancedDraw::draw_calls(Some(100), 100), 1);
+        assert_eq!(InstancedDraw::draw_calls(Some(100), 101), 2);
+        assert_eq!(InstancedDraw::draw_calls(Some(100), 250), 3);
+    }
+
+    #[test]
+    fn snap_to_pixel_grid_rounds_to_device_pixels() {
+        assert_eq!(snap_to_pixel_grid(glam::vec2(10.4, -3.6), glam::Vec2::ONE), glam::vec2(10.0, -4.0));
+
+        // Two device pixels per world unit
+        assert_eq!(snap_to_pixel_grid(glam::vec2(10.4, 10.2), glam::Vec2::splat(2.0)), glam::vec2(10.5, 10.0));
+    }
+
+    #[test]
+    fn instance_buffer_shrinks_after_consecutive_underused_frames() {
+        let mut shrink = ShrinkHysteresis::new(Some(3));
+
+        assert!(!shrink.end_frame(10, 100));
+        assert!(!shrink.end_frame(10, 100));
+
+        // A well used frame restarts the count
+        assert!(!shrink.end_frame(50, 100));
+        assert!(!shrink.end_frame(10, 100));
+        assert!(!shrink.end_frame(10, 100));
+        assert!(shrink.end_frame(10, 100));
+
+        // The count starts over once the buffer is shrunk, frames without a buffer are not counted
+        assert_eq!(shrink.underused_frames, 0);
+        assert!(!shrink.end_frame(0, 0));
+        assert_eq!(shrink.underused_frames, 0);
+    }
+
+    #[test]
+    fn instance_buffer_shrink_can_be_disabled() {
+        let mut shrink = ShrinkHysteresis::new(None);
+
+        assert!((0..1000).all(|_| !shrink.end_frame(0, 100)));
+    }
+
+    #[test]
+    fn sorted_quads_reuse_their_buffers() {
+        let mut quads = QuadsInstanceDataBuffer::new(4, std::mem::size_of::<f32>(), None);
+
+        // Without sort keys the quads are drawn in submission order
+        quads.quads.extend_from_slice(bytemuck::bytes_of(&0.0f32));
+        assert!(quads.sorted_quads().is_none());
+        quads.clear();
+
+        for (value, sort_key) in [(1.0f32, 2.0), (2.0, 0.0), (3.0, 1.0), (4.0, 0.0)] {
+            quads.quads.extend_from_slice(bytemuck::bytes_of(&value));
+            quads.push_sort_key(sort_key);
+        }
+
+        let first = quads.sorted_quads().unwrap().as_ptr();
+
+        // Equal keys keep the submission order
+        assert_eq!(bytemuck::cast_slice::<u8, f32>(&quads.sorted_quads().unwrap()), [2.0, 4.0, 3.0, 1.0]);
+
+        // The next frames sort into the same allocations
+        quads.clear();
+        for (value, sort_key) in [(5.0f32, 1.0), (6.0, 0.0)] {
+            quads.quads.extend_from_slice(bytemuck::bytes_of(&value));
+            quads.push_sort_key(sort_key);
+        }
+
+        assert_eq!(quads.sorted_quads().unwrap().as_ptr(), first);
+        assert_eq!(bytemuck::cast_slice::<u8, f32>(&quads.sorted_quads().unwrap()), [6.0, 5.0]);
+        assert_eq!(quads.sort_order.borrow().capacity(), 4);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn sdf_edge_softness_defaults_to_one_pixel() {
+        assert_eq!(Renderer2D::DEFAULT_SDF_EDGE_SOFTNESS, 1.0);
+    }
+
+    #[test]
+    fn sdf_edge_softness_clamps_negatives() {
+        assert_eq!(clamp_edge_softness(-2.0), 0.0);
+        assert_eq!(clamp_edge_softness(0.0), 0.0);
+        assert_eq!(clamp_edge_softness(2.5), 2.5);
+    }
+}
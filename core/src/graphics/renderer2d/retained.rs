@@ -0,0 +1,124 @@
+use std::cell::{Cell, RefCell};
+
+use wgpu::util::DeviceExt;
+
+use crate::{assets::{texture::{Texture2D, Texture2DCoordinates}, AssetHandle}, graphics::{shapes::Quad, GraphicsContext}};
+
+use super::{z_index_to_depth, BlendMode, BucketKey, DrawParams, InstanceSource, InstancedDraw, MeshId, QuadInstanceData};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetainedBatchHandle(pub(super) usize);
+
+// Quads kept across frames and drawn every frame, only the instances changed since the last
+// submit are uploaded. Culling and pixel snapping do not apply to retained quads
+pub struct RetainedBatch {
+    pub(super) key: BucketKey,
+    params: DrawParams,
+
+    instances: Vec<QuadInstanceData>,
+    dirty: Cell<Option<(usize, usize)>>,
+
+    instance_buffer: RefCell<Option<wgpu::Buffer>>,
+    buffer_len: Cell<usize>,
+}
+
+impl RetainedBatch {
+    pub(super) fn new(texture: AssetHandle<Texture2D>, params: DrawParams) -> Self {
+        Self {
+            key: BucketKey {
+                texture: Some(texture),
+                z_index: params.z_index,
+                sampler: params.sampler_handle(),
+                opaque: params.opaque && params.blend == BlendMode::Alpha,
+                blend: params.blend,
+                stencil: params.stencil,
+                material: None,
+                mask: None,
+                mesh: MeshId::QUAD,
+            },
+            params,
+            instances: Vec::new(),
+            dirty: Cell::new(None),
+            instance_buffer: RefCell::new(None),
+            buffer_len: Cell::new(0),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    pub fn push(&mut self, quad: &Quad, atlas_coords: Texture2DCoordinates) -> usize {
+        self.instances.push(self.instance_data(quad, atlas_coords));
+        self.mark_dirty(self.instances.len() - 1);
+
+        self.instances.len() - 1
+    }
+
+    pub fn set(&mut self, index: usize, quad: &Quad, atlas_coords: Texture2DCoordinates) {
+        self.instances[index] = self.instance_data(quad, atlas_coords);
+        self.mark_dirty(index);
+    }
+
+    pub fn clear(&mut self) {
+        self.instances.clear();
+        self.dirty.set(None);
+    }
+
+    fn instance_data(&self, quad: &Quad, atlas_coords: Texture2DCoordinates) -> QuadInstanceData {
+        let mut model = quad.get_transform();
+        model.w_axis.z = z_index_to_depth(self.params.z_index);
+
+        QuadInstanceData::new(model, quad.color * self.params.tint, atlas_coords, self.params.flip)
+    }
+
+    fn mark_dirty(&self, index: usize) {
+        let dirty = match self.dirty.get() {
+            Some((start, end)) => (start.min(index), end.max(index + 1)),
+            None => (index, index + 1),
+        };
+
+        self.dirty.set(Some(dirty));
+    }
+}
+
+impl InstanceSource for RetainedBatch {
+    fn submit_to_render_pass(&self, context: &GraphicsContext, render_pass: &mut wgpu::RenderPass, draw: InstancedDraw) {
+        if self.instances.is_empty() {
+            return;
+        }
+
+        if self.instance_buffer.borrow().is_none() || self.buffer_len.get() < self.instances.len() {
+            log::info!("Reallocating retained batch instance buffer");
+
+            let instance_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Retained batch instance buffer"),
+                contents: bytemuck::cast_slice(&self.instances),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            });
+
+            self.instance_buffer.replace(Some(instance_buffer));
+            self.buffer_len.set(self.instances.len());
+        }
+        else if let Some((start, end)) = self.dirty.get() {
+            context.queue.write_buffer(
+                self.instance_buffer.borrow().as_ref().unwrap(),
+                (start * std::mem::size_of::<QuadInstanceData>()) as wgpu::BufferAddress,
+                bytemuck::cast_slice(&self.instances[start..end])
+            );
+        }
+
+        self.dirty.set(None);
+
+        let instance_buffer = self.instance_buffer.borrow();
+        let size = self.instances.len() * std::mem::size_of::<QuadInstanceData>();
+
+        render_pass.set_vertex_buffer(1, instance_buffer.as_ref().unwrap().slice(0..size as _));
+        draw.draw(render_pass, self.instances.len());
+    }
+}
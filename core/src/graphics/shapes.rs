@@ -1,16 +1,25 @@
 use std::cell::Cell;
 
 
+// Only the position, size, rotation and color are serialized, the transform is recomputed on load
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Quad {
     position: glam::Vec2,
     size: glam::Vec2,
     rotation: f32,
     pub color: glam::Vec4,
 
+    #[cfg_attr(feature = "serde", serde(skip))]
     transform: Cell<glam::Mat4>,
+    #[cfg_attr(feature = "serde", serde(skip, default = "needs_update"))]
     transform_needs_update: bool,
 }
 
+#[cfg(feature = "serde")]
+fn needs_update() -> bool {
+    true
+}
+
 
 impl Quad {
 
@@ -27,6 +36,18 @@ impl Quad {
         }
     }
 
+    pub fn position(&self) -> glam::Vec2 {
+        self.position
+    }
+
+    pub fn size(&self) -> glam::Vec2 {
+        self.size
+    }
+
+    pub fn rotation(&self) -> f32 {
+        self.rotation
+    }
+
     pub fn set_position(&mut self, position: glam::Vec2) {
         self.position = position;
         self.transform_needs_update = true;
@@ -42,6 +63,16 @@ impl Quad {
         self.transform_needs_update = true;
     }
 
+    // Orbits the quad center around a world space pivot and rotates the quad by the same angle,
+    // in degrees like `rotate`
+    pub fn rotate_around(&mut self, pivot: glam::Vec2, degrees: f32) {
+        let center = self.position + self.size * 0.5;
+        let rotated_center = pivot + glam::Vec2::from_angle(degrees.to_radians()).rotate(center - pivot);
+
+        self.position = rotated_center - self.size * 0.5;
+        self.rotate(degrees);
+    }
+
     pub fn get_transform(&self) -> glam::Mat4 {
         if self.transform_needs_update {
             self.transform.set(Self::compute_transform(self.position, self.size, self.rotation))
@@ -0,0 +1,175 @@
+use image::RgbaImage;
+
+use crate::assets::{texture::{SpriteSheetCoordinates, Texture2D}, AssetHandle};
+
+
+// Monospace font baked in a sprite sheet, glyphs are laid out in character order from `first_char`.
+// Sizes are in world units at scale 1
+pub struct BitmapFont {
+    texture: AssetHandle<Texture2D>,
+    glyphs: SpriteSheetCoordinates,
+    first_char: char,
+    glyph_size: glam::Vec2,
+    line_height: f32,
+    sdf: bool,
+}
+
+impl BitmapFont {
+    pub fn new(texture: AssetHandle<Texture2D>, glyphs: SpriteSheetCoordinates, first_char: char, glyph_size: glam::Vec2) -> Self {
+        Self {
+            texture,
+            glyphs,
+            first_char,
+            glyph_size,
+            line_height: glyph_size.y,
+            sdf: false,
+        }
+    }
+
+    pub fn with_line_height(mut self, line_height: f32) -> Self {
+        self.line_height = line_height;
+        self
+    }
+
+    // The texture is a distance field made with `generate_sdf`, the glyphs stay sharp at any scale.
+    // Plain bitmap fonts are drawn as textured quads which is cheaper
+    pub fn with_sdf(mut self) -> Self {
+        self.sdf = true;
+        self
+    }
+
+    pub fn is_sdf(&self) -> bool {
+        self.sdf
+    }
+
+    pub fn texture(&self) -> AssetHandle<Texture2D> {
+        self.texture
+    }
+
+    pub fn glyph_size(&self) -> glam::Vec2 {
+        self.glyph_size
+    }
+
+    pub fn line_height(&self) -> f32 {
+        self.line_height
+    }
+
+    pub(crate) fn glyph_index(&self, c: char) -> Option<usize> {
+        (c as u32).checked_sub(self.first_char as u32)
+            .map(|index| index as usize)
+            .filter(|&index| index < self.glyphs.len())
+    }
+
+    pub(crate) fn glyphs(&self) -> &SpriteSheetCoordinates {
+        &self.glyphs
+    }
+}
+
+// Turns the glyphs of a bitmap font atlas, opaque where the alpha is at least 0.5, into a signed
+// distance field stored in the alpha: 0.5 on the glyph edges, 1 at `spread` pixels inside and 0 at
+// `spread` pixels outside. Glyphs need `spread` pixels of empty padding in their sprite cell so the
+// fields of neighbouring glyphs don't touch, bake the atlas large and draw it scaled down
+pub fn generate_sdf(atlas: &RgbaImage, spread: u32) -> RgbaImage {
+    let (width, height) = atlas.dimensions();
+    let spread = spread.max(1) as i64;
+    let inside = |x: i64, y: i64| atlas.get_pixel(x as u32, y as u32)[3] >= 128;
+
+    RgbaImage::from_fn(width, height, |x, y| {
+        let (x, y) = (x as i64, y as i64);
+        let is_inside = inside(x, y);
+
+        // Squared distance to the closest pixel on the other side of the edge, pixels farther than
+        // the spread are at the full spread from the edge
+        let mut closest = (spread as f32 + 0.5).powi(2);
+
+        for other_y in (y - spread).max(0)..=(y + spread).min(height as i64 - 1) {
+            for other_x in (x - spread).max(0)..=(x + spread).min(width as i64 - 1) {
+                if inside(other_x, other_y) != is_inside {
+                    let (dx, dy) = ((other_x - x) as f32, (other_y - y) as f32);
+                    closest = closest.min(dx * dx + dy * dy);
+                }
+            }
+        }
+
+        // The edge is half way between the two pixels
+        let distance = (closest.sqrt() - 0.5).min(spread as f32);
+        let signed = if is_inside { distance } else { -distance };
+        let alpha = (0.5 + signed / (2.0 * spread as f32)).clamp(0.0, 1.0);
+
+        image::Rgba([255, 255, 255, (alpha * 255.0).round() as u8])
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextOverflow {
+    // Lines going past the bottom of the bounds are not drawn
+    #[default]
+    Clip,
+    Overflow,
+}
+
+// Breaks the text on whitespace into lines of at most `max_columns` characters, words longer than a
+// line are split. Line breaks in the text are kept
+pub fn wrap_lines(text: &str, max_columns: usize) -> Vec<String> {
+    let max_columns = max_columns.max(1);
+    let mut lines = vec![];
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        let mut line_columns = 0;
+
+        for word in paragraph.split_whitespace() {
+            let mut word = word.chars().collect::<Vec<_>>();
+
+            if line_columns > 0 && line_columns + 1 + word.len() <= max_columns {
+                line.push(' ');
+                line.extend(&word);
+                line_columns += 1 + word.len();
+                continue;
+            }
+
+            if line_columns > 0 {
+                lines.push(std::mem::take(&mut line));
+            }
+
+            while word.len() > max_columns {
+                lines.push(word.drain(..max_columns).collect());
+            }
+
+            line_columns = word.len();
+            line.extend(word);
+        }
+
+        lines.push(line);
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_sdf_encodes_distance_to_edge() {
+        // 12x12 square in the middle of a 24x24 image
+        let atlas = RgbaImage::from_fn(24, 24, |x, y| {
+            let inside = (6..18).contains(&x) && (6..18).contains(&y);
+            image::Rgba([255, 255, 255, if inside { 255 } else { 0 }])
+        });
+
+        let sdf = generate_sdf(&atlas, 4);
+        let alpha = |x, y| sdf.get_pixel(x, y)[3];
+
+        assert_eq!(sdf.dimensions(), atlas.dimensions());
+
+        // Both sides of the edge are half a pixel away from it
+        assert!(alpha(6, 12) > 128 && alpha(5, 12) < 128);
+        assert_eq!(alpha(6, 12) - 128, 127 - alpha(5, 12));
+
+        // Past the spread the distance is clamped
+        assert_eq!(alpha(12, 12), 255);
+        assert_eq!(alpha(0, 0), 0);
+        assert!(alpha(8, 12) > alpha(7, 12));
+    }
+}
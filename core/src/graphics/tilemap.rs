@@ -0,0 +1,85 @@
+use crate::{assets::{texture::{SpriteSheetCoordinates, Texture2D}, AssetHandle}, graphics::{camera::Camera2D, renderer2d::{DrawParams, Renderer2D}, shapes::Quad}};
+
+
+pub struct TileMap {
+    texture: AssetHandle<Texture2D>,
+    sprite_sheet: SpriteSheetCoordinates,
+
+    columns: usize,
+    rows: usize,
+    tiles: Vec<Option<usize>>,
+
+    pub position: glam::Vec2,
+    pub tile_size: glam::Vec2,
+    pub z_index: i32,
+}
+
+impl TileMap {
+    pub fn new(
+        texture: AssetHandle<Texture2D>,
+        sprite_sheet: SpriteSheetCoordinates,
+        columns: usize,
+        rows: usize,
+        tile_size: glam::Vec2
+    ) -> Self {
+        Self {
+            texture,
+            sprite_sheet,
+            columns,
+            rows,
+            tiles: vec![None; columns * rows],
+            position: glam::Vec2::ZERO,
+            tile_size,
+            z_index: 0,
+        }
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn get_tile(&self, x: usize, y: usize) -> Option<usize> {
+        self.tile_index(x, y).and_then(|i| self.tiles[i])
+    }
+
+    // Sets the sprite sheet index of the tile, None makes the tile empty
+    pub fn set_tile(&mut self, x: usize, y: usize, tile: Option<usize>) {
+        if let Some(i) = self.tile_index(x, y) {
+            self.tiles[i] = tile;
+        }
+    }
+
+    pub fn render(&self, renderer: &mut Renderer2D, camera: &Camera2D) {
+        let (visible_min, visible_max) = camera.visible_rect();
+
+        let first = ((visible_min - self.position) / self.tile_size).floor().max(glam::Vec2::ZERO);
+        let last = ((visible_max - self.position) / self.tile_size).ceil()
+            .min(glam::vec2(self.columns as f32, self.rows as f32));
+
+        let params = DrawParams {
+            z_index: self.z_index,
+            ..Default::default()
+        };
+
+        for y in first.y as usize..last.y.max(0.0) as usize {
+            for x in first.x as usize..last.x.max(0.0) as usize {
+                let Some(coords) = self.get_tile(x, y).and_then(|tile| self.sprite_sheet.get_coords_by_index(tile)) else {
+                    continue;
+                };
+
+                let position = self.position + glam::vec2(x as f32, y as f32) * self.tile_size;
+                let quad = Quad::new(position, self.tile_size, 0.0);
+
+                renderer.draw_quad_textured_ex(&quad, self.texture, coords, params);
+            }
+        }
+    }
+
+    fn tile_index(&self, x: usize, y: usize) -> Option<usize> {
+        (x < self.columns && y < self.rows).then_some(y * self.columns + x)
+    }
+}
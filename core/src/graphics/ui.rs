@@ -0,0 +1,231 @@
+use winit::event::MouseButton;
+
+use crate::{application::input::MouseInput, assets::{texture::Texture2D, AssetHandle}, graphics::{renderer2d::{DrawParams, Renderer2D}, shapes::Quad}};
+
+pub mod layout;
+
+
+// Absolute positioned widgets drawn with quads. Their rects are in the cursor space (physical pixels)
+// so they are meant to be drawn with a camera sized to `GraphicsContext::physical_size`
+
+#[derive(Debug, Clone, Copy)]
+pub struct UiStyle {
+    pub background: glam::Vec4,
+    pub hovered: glam::Vec4,
+    pub pressed: glam::Vec4,
+    pub accent: glam::Vec4,
+    pub z_index: i32,
+}
+
+impl Default for UiStyle {
+    fn default() -> Self {
+        Self {
+            background: glam::vec4(0.25, 0.25, 0.3, 1.0),
+            hovered: glam::vec4(0.35, 0.35, 0.42, 1.0),
+            pressed: glam::vec4(0.18, 0.18, 0.22, 1.0),
+            accent: glam::vec4(0.3, 0.55, 0.9, 1.0),
+            z_index: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ButtonResponse {
+    pub hovered: bool,
+    pub pressed: bool,
+    // Released over the button after being pressed on it
+    pub clicked: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Button {
+    pub position: glam::Vec2,
+    pub size: glam::Vec2,
+    pub icon: Option<AssetHandle<Texture2D>>,
+    pub style: UiStyle,
+}
+
+impl Button {
+    pub fn new(position: glam::Vec2, size: glam::Vec2) -> Self {
+        Self {
+            position,
+            size,
+            icon: None,
+            style: UiStyle::default(),
+        }
+    }
+
+    pub fn with_icon(mut self, icon: AssetHandle<Texture2D>) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    pub fn show(&self, renderer: &mut Renderer2D, mouse: &MouseInput) -> ButtonResponse {
+        let hovered = contains(self.position, self.size, mouse.position());
+        let pressed_on = mouse.press_position(MouseButton::Left)
+            .is_some_and(|position| contains(self.position, self.size, position));
+
+        let response = ButtonResponse {
+            hovered,
+            pressed: pressed_on && mouse.is_button_pressed(MouseButton::Left),
+            clicked: pressed_on && hovered && mouse.is_button_just_released(MouseButton::Left),
+        };
+
+        let color = if response.pressed {
+            self.style.pressed
+        }
+        else if hovered {
+            self.style.hovered
+        }
+        else {
+            self.style.background
+        };
+
+        draw_rect(renderer, self.position, self.size, color, self.style.z_index);
+
+        if let Some(icon) = self.icon {
+            let params = DrawParams { z_index: self.style.z_index + 1, ..Default::default() };
+            renderer.draw_quad_textured_ex(&Quad::new(self.position, self.size, 0.0), icon, Default::default(), params);
+        }
+
+        response
+    }
+}
+
+// Draws a pre-rendered texture such as an icon or baked text, `TextLabel` draws text with the renderer font
+#[derive(Debug, Clone, Copy)]
+pub struct Label {
+    pub position: glam::Vec2,
+    pub size: glam::Vec2,
+    pub texture: AssetHandle<Texture2D>,
+    pub tint: glam::Vec4,
+    pub z_index: i32,
+}
+
+impl Label {
+    pub fn new(position: glam::Vec2, size: glam::Vec2, texture: AssetHandle<Texture2D>) -> Self {
+        Self {
+            position,
+            size,
+            texture,
+            tint: glam::Vec4::ONE,
+            z_index: UiStyle::default().z_index + 1,
+        }
+    }
+
+    pub fn show(&self, renderer: &mut Renderer2D) {
+        let params = DrawParams {
+            tint: self.tint,
+            z_index: self.z_index,
+            ..Default::default()
+        };
+
+        renderer.draw_quad_textured_ex(&Quad::new(self.position, self.size, 0.0), self.texture, Default::default(), params);
+    }
+}
+
+// Text drawn with the font set by `Renderer2D::set_font` from its top left corner, nothing is drawn
+// without a font
+#[derive(Debug, Clone)]
+pub struct TextLabel {
+    pub position: glam::Vec2,
+    pub text: String,
+    pub scale: f32,
+    pub color: glam::Vec4,
+    pub z_index: i32,
+}
+
+impl TextLabel {
+    pub fn new(position: glam::Vec2, text: impl Into<String>) -> Self {
+        Self {
+            position,
+            text: text.into(),
+            scale: 1.0,
+            color: glam::Vec4::ONE,
+            z_index: UiStyle::default().z_index + 1,
+        }
+    }
+
+    pub fn show(&self, renderer: &mut Renderer2D) {
+        let params = DrawParams { z_index: self.z_index, ..Default::default() };
+        renderer.draw_text_ex(&self.text, self.position, self.scale, self.color, params);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Slider {
+    pub position: glam::Vec2,
+    pub size: glam::Vec2,
+    pub min: f32,
+    pub max: f32,
+    pub style: UiStyle,
+}
+
+impl Slider {
+    pub fn new(position: glam::Vec2, size: glam::Vec2, min: f32, max: f32) -> Self {
+        Self {
+            position,
+            size,
+            min,
+            max,
+            style: UiStyle::default(),
+        }
+    }
+
+    // The value follows the cursor while the slider is dragged, returns whether it changed
+    pub fn show(&self, renderer: &mut Renderer2D, mouse: &MouseInput, value: &mut f32) -> bool {
+        let dragged = mouse.is_button_pressed(MouseButton::Left) && mouse.press_position(MouseButton::Left)
+            .is_some_and(|position| contains(self.position, self.size, position));
+
+        let old_value = *value;
+
+        if dragged && self.size.x > 0.0 {
+            let t = ((mouse.position().x - self.position.x) / self.size.x).clamp(0.0, 1.0);
+            *value = self.min + t * (self.max - self.min);
+        }
+
+        let range = self.max - self.min;
+        let t = if range != 0.0 { ((*value - self.min) / range).clamp(0.0, 1.0) } else { 0.0 };
+
+        let handle_width = self.size.y * 0.5;
+        let handle_color = if dragged {
+            self.style.pressed
+        }
+        else if contains(self.position, self.size, mouse.position()) {
+            self.style.hovered
+        }
+        else {
+            self.style.accent
+        };
+
+        draw_rect(renderer, self.position, self.size, self.style.background, self.style.z_index);
+
+        if t > 0.0 {
+            draw_rect(renderer, self.position, glam::vec2(self.size.x * t, self.size.y), self.style.accent, self.style.z_index + 1);
+        }
+
+        draw_rect(
+            renderer,
+            glam::vec2(self.position.x + self.size.x * t - handle_width * 0.5, self.position.y),
+            glam::vec2(handle_width, self.size.y),
+            handle_color,
+            self.style.z_index + 2
+        );
+
+        *value != old_value
+    }
+}
+
+fn contains(position: glam::Vec2, size: glam::Vec2, point: glam::Vec2) -> bool {
+    point.cmpge(position).all() && point.cmplt(position + size).all()
+}
+
+fn draw_rect(renderer: &mut Renderer2D, position: glam::Vec2, size: glam::Vec2, color: glam::Vec4, z_index: i32) {
+    let params = DrawParams {
+        tint: color,
+        z_index,
+        ..Default::default()
+    };
+
+    renderer.draw_quad_ex(&Quad::new(position, size, 0.0), params);
+}
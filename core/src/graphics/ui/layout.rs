@@ -0,0 +1,81 @@
+use crate::{application::event::ApplicationEvent, graphics::shapes::Quad};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Anchor {
+    #[default]
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    // Fraction of the free space (surface minus element size) placed before the element
+    fn alignment(self) -> glam::Vec2 {
+        match self {
+            Self::TopLeft => glam::vec2(0.0, 0.0),
+            Self::Top => glam::vec2(0.5, 0.0),
+            Self::TopRight => glam::vec2(1.0, 0.0),
+            Self::Left => glam::vec2(0.0, 0.5),
+            Self::Center => glam::vec2(0.5, 0.5),
+            Self::Right => glam::vec2(1.0, 0.5),
+            Self::BottomLeft => glam::vec2(0.0, 1.0),
+            Self::Bottom => glam::vec2(0.5, 1.0),
+            Self::BottomRight => glam::vec2(1.0, 1.0),
+        }
+    }
+}
+
+// Top left corner of an element of `size` anchored in a surface of `surface_size`. The offset is a
+// margin from the anchored edges, positive values move the element inside the surface, and a plain
+// shift on the centered axes
+pub fn anchored_position(anchor: Anchor, offset: glam::Vec2, size: glam::Vec2, surface_size: glam::Vec2) -> glam::Vec2 {
+    let alignment = anchor.alignment();
+    let direction = glam::Vec2::select(alignment.cmpeq(glam::Vec2::ONE), -glam::Vec2::ONE, glam::Vec2::ONE);
+
+    (surface_size - size) * alignment + offset * direction
+}
+
+// HUD element rect kept anchored to the surface, `handle_event` follows the window size
+#[derive(Debug, Clone, Copy)]
+pub struct AnchoredRect {
+    pub anchor: Anchor,
+    pub offset: glam::Vec2,
+    pub size: glam::Vec2,
+    surface_size: glam::Vec2,
+}
+
+impl AnchoredRect {
+    pub fn new(anchor: Anchor, offset: glam::Vec2, size: glam::Vec2, surface_size: glam::Vec2) -> Self {
+        Self {
+            anchor,
+            offset,
+            size,
+            surface_size,
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &ApplicationEvent) {
+        if let ApplicationEvent::Resized { width, height } = event {
+            self.set_surface_size(glam::vec2(*width as f32, *height as f32));
+        }
+    }
+
+    pub fn set_surface_size(&mut self, surface_size: glam::Vec2) {
+        self.surface_size = surface_size;
+    }
+
+    pub fn position(&self) -> glam::Vec2 {
+        anchored_position(self.anchor, self.offset, self.size, self.surface_size)
+    }
+
+    pub fn quad(&self) -> Quad {
+        Quad::new(self.position(), self.size, 0.0)
+    }
+}
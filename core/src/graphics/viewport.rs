@@ -0,0 +1,119 @@
+use crate::graphics::camera::Camera2D;
+
+
+// Area of the window drawn with its own camera, for split screens and minimaps. The rect is a
+// fraction of the renderer viewport so it follows resizes, (0, 0) and (1, 1) cover all of it
+#[derive(Debug, Clone)]
+pub struct ScreenViewport {
+    pub position: glam::Vec2,
+    pub size: glam::Vec2,
+    pub camera: Camera2D,
+}
+
+impl ScreenViewport {
+    pub fn new(position: glam::Vec2, size: glam::Vec2, camera: Camera2D) -> Self {
+        Self {
+            position,
+            size,
+            camera,
+        }
+    }
+
+    // Position and size in physical pixels for a renderer viewport of `viewport_size`,
+    // see `GraphicsContext::viewport_rect`
+    pub fn pixel_rect(&self, viewport_size: glam::Vec2) -> (glam::Vec2, glam::Vec2) {
+        (self.position * viewport_size, self.size * viewport_size)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewportHit {
+    // Index of the viewport in the slice given to `hit_test`
+    pub index: usize,
+    // Cursor position in pixels relative to the viewport top left corner
+    pub local_position: glam::Vec2,
+    pub world_position: glam::Vec2,
+}
+
+// Finds the viewport under the cursor. Viewports are not registered anywhere, the caller passes the
+// ones it draws in draw order, later viewports are drawn on top and win when they overlap.
+// `cursor` is relative to the renderer viewport like the positions of the application events, with
+// letterboxing the bars are outside of it and `viewport_size` is the letterboxed size
+pub fn hit_test(viewports: &[ScreenViewport], cursor: glam::Vec2, viewport_size: glam::Vec2) -> Option<ViewportHit> {
+    viewports.iter().enumerate().rev().find_map(|(index, viewport)| {
+        let (position, size) = viewport.pixel_rect(viewport_size);
+        let local_position = cursor - position;
+
+        let inside = local_position.cmpge(glam::Vec2::ZERO).all() && local_position.cmplt(size).all();
+
+        inside.then(|| ViewportHit {
+            index,
+            local_position,
+            world_position: viewport.camera.screen_to_world(local_position, size),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::camera::FitMode;
+
+    fn split_screen() -> Vec<ScreenViewport> {
+        vec![
+            ScreenViewport::new(glam::Vec2::ZERO, glam::vec2(0.5, 1.0), Camera2D::new(400.0, 600.0)),
+            ScreenViewport::new(glam::vec2(0.5, 0.0), glam::vec2(0.5, 1.0), Camera2D::new(400.0, 600.0)),
+        ]
+    }
+
+    #[test]
+    fn hit_test_finds_the_viewport_under_the_cursor() {
+        let hit = hit_test(&split_screen(), glam::vec2(500.0, 150.0), glam::vec2(800.0, 600.0));
+
+        assert_eq!(hit, Some(ViewportHit {
+            index: 1,
+            local_position: glam::vec2(100.0, 150.0),
+            world_position: glam::vec2(100.0, 150.0),
+        }));
+    }
+
+    #[test]
+    fn hit_test_misses_outside_every_viewport() {
+        let viewports = [ScreenViewport::new(glam::Vec2::ZERO, glam::Vec2::splat(0.5), Camera2D::new(400.0, 300.0))];
+        let viewport_size = glam::vec2(800.0, 600.0);
+
+        assert_eq!(hit_test(&viewports, glam::vec2(600.0, 100.0), viewport_size), None);
+        assert_eq!(hit_test(&viewports, glam::vec2(-1.0, 100.0), viewport_size), None);
+        assert_eq!(hit_test(&[], glam::vec2(100.0, 100.0), viewport_size), None);
+    }
+
+    #[test]
+    fn hit_test_prefers_the_last_overlapping_viewport() {
+        let mut viewports = split_screen();
+        // Minimap drawn over the top right corner of the second viewport
+        viewports.push(ScreenViewport::new(glam::vec2(0.75, 0.0), glam::Vec2::splat(0.25), Camera2D::new(1000.0, 1000.0)));
+
+        let hit = hit_test(&viewports, glam::vec2(700.0, 75.0), glam::vec2(800.0, 600.0)).unwrap();
+
+        assert_eq!(hit.index, 2);
+        assert_eq!(hit.local_position, glam::vec2(100.0, 75.0));
+        assert!(hit.world_position.abs_diff_eq(glam::vec2(500.0, 500.0), 1e-3));
+    }
+
+    #[test]
+    fn hit_test_maps_letterboxed_cursor_to_world() {
+        // A 16:9 design in a 4:3 window, the renderer viewport is the 800x450 area between the bars
+        let camera = Camera2D::with_fit_mode(320.0, 180.0, FitMode::Letterbox);
+        let viewports = [ScreenViewport::new(glam::Vec2::ZERO, glam::Vec2::ONE, camera)];
+        let viewport_size = glam::vec2(800.0, 450.0);
+
+        let center = hit_test(&viewports, glam::vec2(400.0, 225.0), viewport_size).unwrap();
+        assert!(center.world_position.abs_diff_eq(glam::vec2(160.0, 90.0), 1e-3));
+
+        let corner = hit_test(&viewports, glam::vec2(0.0, 0.0), viewport_size).unwrap();
+        assert!(corner.world_position.abs_diff_eq(glam::Vec2::ZERO, 1e-3));
+
+        // The bottom bar is outside of the renderer viewport
+        assert_eq!(hit_test(&viewports, glam::vec2(400.0, 460.0), viewport_size), None);
+    }
+}
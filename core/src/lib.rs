@@ -1,16 +1,31 @@
-use std::time::{Duration, Instant};
+use std::{sync::OnceLock, time::{Duration, Instant}};
 
 pub mod application;
 pub mod graphics;
 pub mod assets;
+pub mod scene;
+pub mod math;
 
 pub mod export;
 
 
+static ENGINE_START: OnceLock<Instant> = OnceLock::new();
+
+// Monotonic time since the engine clock was started by the first call, `Application::run` starts it
+pub fn engine_time() -> Duration {
+    ENGINE_START.get_or_init(Instant::now).elapsed()
+}
+
 pub struct Timer {
     current_instant: Instant
 }
 
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Timer {
     pub fn new() -> Self {
         Self {
@@ -22,9 +37,11 @@ impl Timer {
         self.current_instant.elapsed()
     }
 
+    // Reads the clock once so no time is lost between two restarts
     pub fn restart(&mut self) -> Duration {
-        let elapsed = self.elapsed();
-        self.current_instant = Instant::now();
+        let now = Instant::now();
+        let elapsed = now - self.current_instant;
+        self.current_instant = now;
 
         elapsed
     }
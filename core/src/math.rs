@@ -0,0 +1,29 @@
+use std::ops::{Add, Mul, Sub};
+
+pub mod rng;
+
+
+// Frame rate independent exponential smoothing towards `target`, `lambda` is how fast the distance
+// shrinks per second. Stepping with many small `dt` gives the same result as one step of their sum,
+// unlike a lerp with a fixed factor per frame
+pub fn damp<T>(current: T, target: T, lambda: f32, dt: f32) -> T
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>
+{
+    target + (current - target) * (-lambda * dt).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn damp_is_frame_rate_independent() {
+        let (start, target, lambda) = (glam::vec2(10.0, -4.0), glam::vec2(2.0, 6.0), 3.0);
+
+        let single_step = damp(start, target, lambda, 1.0);
+        let many_steps = (0..1000).fold(start, |current, _| damp(current, target, lambda, 0.001));
+
+        assert!(single_step.abs_diff_eq(many_steps, 1e-4), "{single_step} != {many_steps}");
+    }
+}
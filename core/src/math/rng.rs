@@ -0,0 +1,66 @@
+// PCG32 (XSH RR variant), small and fast enough for gameplay randomness, not for cryptography
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+    increment: u64,
+}
+
+impl Rng {
+    const MULTIPLIER: u64 = 6364136223846793005;
+
+    pub fn new(seed: u64) -> Self {
+        Self::with_stream(seed, 0xda3e39cb94b95bdb)
+    }
+
+    // Rngs with the same seed but different streams produce independent sequences
+    pub fn with_stream(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old_state = self.state;
+        self.state = old_state.wrapping_mul(Self::MULTIPLIER).wrapping_add(self.increment);
+
+        let xorshifted = (((old_state >> 18) ^ old_state) >> 27) as u32;
+        let rotation = (old_state >> 59) as u32;
+
+        xorshifted.rotate_right(rotation)
+    }
+
+    // Uniform in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 * (1.0 / (1u32 << 24) as f32)
+    }
+
+    pub fn next_f32_range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    pub fn next_u32_range(&mut self, min: u32, max: u32) -> u32 {
+        if max <= min {
+            return min;
+        }
+
+        min + ((self.next_u32() as u64 * (max - min) as u64) >> 32) as u32
+    }
+
+    pub fn next_vec2_in_rect(&mut self, min: glam::Vec2, max: glam::Vec2) -> glam::Vec2 {
+        glam::vec2(
+            self.next_f32_range(min.x, max.x),
+            self.next_f32_range(min.y, max.y),
+        )
+    }
+
+    pub fn gen_bool(&mut self, probability: f32) -> bool {
+        self.next_f32() < probability
+    }
+}
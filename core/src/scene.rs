@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use crate::{assets::{texture::{Texture2D, Texture2DCoordinates}, AssetHandle}, graphics::{renderer2d::Renderer2D, shapes::Quad}};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Entity(u32);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Sprite {
+    pub texture: AssetHandle<Texture2D>,
+    pub coords: Texture2DCoordinates,
+    pub size: glam::Vec2,
+    pub color: glam::Vec4,
+}
+
+impl Sprite {
+    pub fn new(texture: AssetHandle<Texture2D>, size: glam::Vec2) -> Self {
+        Self {
+            texture,
+            coords: Default::default(),
+            size,
+            color: glam::Vec4::ONE,
+        }
+    }
+}
+
+pub struct ComponentStorage<T> {
+    components: HashMap<Entity, T>,
+}
+
+impl<T> Default for ComponentStorage<T> {
+    fn default() -> Self {
+        Self {
+            components: HashMap::new(),
+        }
+    }
+}
+
+impl<T> ComponentStorage<T> {
+    pub fn insert(&mut self, entity: Entity, component: T) -> Option<T> {
+        self.components.insert(entity, component)
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        self.components.remove(&entity)
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        self.components.get(&entity)
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        self.components.get_mut(&entity)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.components.iter().map(|(entity, component)| (*entity, component))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.components.iter_mut().map(|(entity, component)| (*entity, component))
+    }
+}
+
+#[derive(Default)]
+pub struct Scene {
+    next_entity: u32,
+    entities: Vec<Entity>,
+
+    pub positions: ComponentStorage<glam::Vec2>,
+    pub velocities: ComponentStorage<glam::Vec2>,
+    pub sprites: ComponentStorage<Sprite>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn(&mut self) -> Entity {
+        let entity = Entity(self.next_entity);
+        self.next_entity += 1;
+
+        self.entities.push(entity);
+
+        entity
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        self.entities.retain(|e| *e != entity);
+
+        self.positions.remove(entity);
+        self.velocities.remove(entity);
+        self.sprites.remove(entity);
+    }
+
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub fn movement_system(&mut self, dt: f32) {
+        for (entity, velocity) in self.velocities.iter() {
+            if let Some(position) = self.positions.get_mut(entity) {
+                *position += *velocity * dt;
+            }
+        }
+    }
+
+    pub fn render_system(&self, renderer: &mut Renderer2D) {
+        for entity in &self.entities {
+            let (Some(position), Some(sprite)) = (self.positions.get(*entity), self.sprites.get(*entity)) else {
+                continue;
+            };
+
+            let mut quad = Quad::new(*position, sprite.size, 0.0);
+            quad.color = sprite.color;
+
+            renderer.draw_quad_textured(&quad, sprite.texture, sprite.coords);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn movement_system_moves_entities_with_velocity() {
+        let mut scene = Scene::new();
+
+        let moving = scene.spawn();
+        let still = scene.spawn();
+        let no_position = scene.spawn();
+
+        scene.positions.insert(moving, glam::vec2(1.0, 2.0));
+        scene.velocities.insert(moving, glam::vec2(2.0, -4.0));
+        scene.positions.insert(still, glam::vec2(5.0, 5.0));
+        scene.velocities.insert(no_position, glam::vec2(1.0, 1.0));
+
+        scene.movement_system(0.5);
+
+        assert_eq!(scene.entities(), &[moving, still, no_position]);
+        assert_eq!(scene.positions.get(moving), Some(&glam::vec2(2.0, 0.0)));
+        assert_eq!(scene.positions.get(still), Some(&glam::vec2(5.0, 5.0)));
+        assert_eq!(scene.positions.get(no_position), None);
+
+        let mut positions = scene.positions.iter().map(|(entity, position)| (entity, *position)).collect::<Vec<_>>();
+        positions.sort_by_key(|(entity, _)| entity.0);
+
+        assert_eq!(positions, vec![(moving, glam::vec2(2.0, 0.0)), (still, glam::vec2(5.0, 5.0))]);
+    }
+
+    #[test]
+    fn despawn_removes_entity_and_components() {
+        let mut scene = Scene::new();
+
+        let first = scene.spawn();
+        let second = scene.spawn();
+
+        scene.positions.insert(first, glam::Vec2::ZERO);
+        scene.velocities.insert(first, glam::Vec2::ONE);
+        scene.positions.insert(second, glam::Vec2::ONE);
+
+        scene.despawn(first);
+        scene.movement_system(1.0);
+
+        assert_eq!(scene.entities(), &[second]);
+        assert!(scene.velocities.get(first).is_none());
+        assert_eq!(scene.positions.iter().collect::<Vec<_>>(), vec![(second, &glam::Vec2::ONE)]);
+
+        // Ids are not reused after a despawn
+        let third = scene.spawn();
+        assert_ne!(third, first);
+        assert_eq!(scene.entities(), &[second, third]);
+    }
+}